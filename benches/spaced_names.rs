@@ -0,0 +1,33 @@
+//! A crude benchmark of quoting a corpus of names which contain spaces, which is the common case
+//! for single-quoted output. Run with `cargo bench`.
+
+use quotemeta::quotemeta;
+use std::{hint::black_box, time::Instant};
+
+fn main() {
+    let words = ["Documents", "and", "Settings", "My", "Music", "Program", "Files", "(x86)"];
+    let corpus: Vec<String> = (0 .. 10_000)
+        .map(|i| {
+            let len = 2 + i % 5;
+            (0 .. len).map(|j| words[(i + j) % words.len()]).collect::<Vec<_>>().join(" ")
+        })
+        .collect();
+    let bytes: usize = corpus.iter().map(String::len).sum();
+
+    let rounds = 100;
+    let start = Instant::now();
+    for _ in 0 .. rounds {
+        for name in &corpus {
+            black_box(quotemeta(black_box(name)));
+        }
+    }
+    let elapsed = start.elapsed();
+    println!(
+        "quoted {} names ({} bytes) {} times in {:?} ({:.1} MB/s)",
+        corpus.len(),
+        bytes,
+        rounds,
+        elapsed,
+        (bytes * rounds) as f64 / elapsed.as_secs_f64() / 1e6
+    );
+}
@@ -0,0 +1,173 @@
+//! Summarising which kinds of byte a string contains, without quoting it.
+
+use crate::Tier;
+use std::{fmt, iter::FromIterator};
+
+/// A kind of byte, as far as quoting is concerned.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[non_exhaustive]
+pub enum ByteClass {
+    /// A byte which can be used without quoting, such as a letter or `/`.
+    Safe,
+    /// A byte which is safe provided it is single-quoted, such as a space or `$`.
+    SingleOnly,
+    /// An ASCII control character, 0x00 to 0x1f or 0x7f, which must be escaped within `$'...'`.
+    Control,
+    /// A byte with the high bit set, 0x80 to 0xff, which must be escaped within `$'...'`. Any
+    /// non-ASCII text is made of these.
+    HighBit,
+    /// A single quote or backslash, which must be backslash-escaped within `$'...'`.
+    QuoteOrBackslash,
+}
+
+impl ByteClass {
+    /// All the classes, in order.
+    pub const ALL: [Self; 5] =
+        [Self::Safe, Self::SingleOnly, Self::Control, Self::HighBit, Self::QuoteOrBackslash];
+
+    /// Returns the class of the byte.
+    #[must_use]
+    pub const fn of(c: u8) -> Self {
+        match (Tier::of_byte(c), c) {
+            (Tier::Bare, _) => Self::Safe,
+            (Tier::Single, _) => Self::SingleOnly,
+            (Tier::CQuoted, 0x80 ..= 0xff) => Self::HighBit,
+            (Tier::CQuoted, b'\'' | b'\\') => Self::QuoteOrBackslash,
+            (Tier::CQuoted, _) => Self::Control,
+        }
+    }
+
+    /// Returns the bit for the class in a [`ByteClassSet`].
+    const fn bit(self) -> u8 {
+        match self {
+            Self::Safe => 1,
+            Self::SingleOnly => 2,
+            Self::Control => 4,
+            Self::HighBit => 8,
+            Self::QuoteOrBackslash => 16,
+        }
+    }
+}
+
+impl fmt::Display for ByteClass {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Safe => "safe characters",
+            Self::SingleOnly => "characters which need quoting",
+            Self::Control => "control characters",
+            Self::HighBit => "high bytes",
+            Self::QuoteOrBackslash => "quotes or backslashes",
+        })
+    }
+}
+
+/// A set of [`ByteClass`]es, as returned by [`byte_classes_present`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct ByteClassSet(u8);
+
+impl ByteClassSet {
+    /// Creates an empty set.
+    #[must_use]
+    pub const fn new() -> Self { Self(0) }
+
+    /// Adds a class to the set.
+    pub const fn insert(&mut self, class: ByteClass) { self.0 |= class.bit(); }
+
+    /// Returns whether the set contains the class.
+    #[must_use]
+    pub const fn contains(self, class: ByteClass) -> bool { self.0 & class.bit() != 0 }
+
+    /// Returns whether the set is empty, which it is only for an empty string.
+    #[must_use]
+    pub const fn is_empty(self) -> bool { self.0 == 0 }
+
+    /// Returns an iterator over the classes in the set, in order.
+    pub fn iter(self) -> impl Iterator<Item = ByteClass> {
+        ByteClass::ALL.iter().copied().filter(move |&class| self.contains(class))
+    }
+}
+
+impl FromIterator<ByteClass> for ByteClassSet {
+    fn from_iter<I: IntoIterator<Item = ByteClass>>(iter: I) -> Self {
+        let mut set = Self::new();
+        iter.into_iter().for_each(|class| set.insert(class));
+        set
+    }
+}
+
+/// Returns the set of [`ByteClass`]es which appear in the given bytes.
+///
+/// This is a single pass over the input which builds nothing, so a tool can cheaply report what
+/// makes a string awkward, such as "this filename contains control characters and high bytes",
+/// without quoting it. The [`Tier`] needed by the string follows from the classes present.
+///
+/// ```
+/// use quotemeta::{byte_classes_present, ByteClass};
+///
+/// let classes = byte_classes_present(b"caf\xc3\xa9\n");
+/// let names: Vec<String> = classes.iter().map(|class| class.to_string()).collect();
+/// assert_eq!(names, ["safe characters", "control characters", "high bytes"]);
+/// assert!(!classes.contains(ByteClass::SingleOnly));
+/// ```
+#[must_use]
+pub fn byte_classes_present(s: &[u8]) -> ByteClassSet {
+    s.iter().map(|&c| ByteClass::of(c)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{byte_classes_present, quotemeta, ByteClass, ByteClassSet};
+    #[cfg(unix)] use std::os::unix::ffi::OsStrExt;
+    use std::ffi::OsStr;
+
+    #[test]
+    fn test_byte_class() {
+        assert_eq!(ByteClass::of(b'a'), ByteClass::Safe);
+        assert_eq!(ByteClass::of(b'/'), ByteClass::Safe);
+        assert_eq!(ByteClass::of(b' '), ByteClass::SingleOnly);
+        assert_eq!(ByteClass::of(b'$'), ByteClass::SingleOnly);
+        assert_eq!(ByteClass::of(0), ByteClass::Control);
+        assert_eq!(ByteClass::of(b'\n'), ByteClass::Control);
+        assert_eq!(ByteClass::of(0x7f), ByteClass::Control);
+        assert_eq!(ByteClass::of(0x80), ByteClass::HighBit);
+        assert_eq!(ByteClass::of(0xff), ByteClass::HighBit);
+        assert_eq!(ByteClass::of(b'\''), ByteClass::QuoteOrBackslash);
+        assert_eq!(ByteClass::of(b'\\'), ByteClass::QuoteOrBackslash);
+    }
+
+    #[test]
+    fn test_byte_classes_present() {
+        assert!(byte_classes_present(b"").is_empty());
+        let safe = byte_classes_present(b"/usr/bin/env");
+        assert_eq!(safe.iter().collect::<Vec<_>>(), [ByteClass::Safe]);
+
+        // Try every combination of classes, and check that the tier follows from them.
+        let samples: [(ByteClass, u8); 5] = [
+            (ByteClass::Safe, b'x'),
+            (ByteClass::SingleOnly, b' '),
+            (ByteClass::Control, b'\t'),
+            (ByteClass::HighBit, 0xe9),
+            (ByteClass::QuoteOrBackslash, b'\''),
+        ];
+        for mask in 0 .. 1 << samples.len() {
+            let chosen: Vec<(ByteClass, u8)> = samples
+                .iter()
+                .enumerate()
+                .filter(|&(bit, _)| mask & (1 << bit) != 0)
+                .map(|(_, &sample)| sample)
+                .collect();
+            let s: Vec<u8> = chosen.iter().map(|&(_, c)| c).collect();
+            let classes = byte_classes_present(&s);
+            assert_eq!(classes, chosen.iter().map(|&(class, _)| class).collect::<ByteClassSet>());
+            for &(class, _) in &samples {
+                let expected = chosen.iter().any(|&(other, _)| other == class);
+                assert_eq!(classes.contains(class), expected, "{s:?}");
+            }
+            let quoted = quotemeta(OsStr::from_bytes(&s));
+            let c_quoted = classes.iter().any(|class| class > ByteClass::SingleOnly);
+            let single_quoted = !c_quoted && classes.contains(ByteClass::SingleOnly);
+            assert_eq!(c_quoted, quoted.starts_with("$'"), "{quoted:?}");
+            assert_eq!(single_quoted, quoted.starts_with('\''), "{quoted:?}");
+        }
+    }
+}
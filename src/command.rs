@@ -0,0 +1,410 @@
+//! Quoting whole command lines, rather than individual arguments.
+
+use crate::{is_printable, quotemeta, FirstBytePolicy, NotPortable, Quoter, Shell};
+#[cfg(unix)] use std::os::unix::ffi::OsStrExt;
+use std::{collections::HashMap, fmt, path::Path, str};
+
+/// Quotes the word with the given quoter, writing an empty word as `''` so that it is not lost.
+fn quoted_word(quoter: &Quoter, s: impl AsRef<Path>) -> String {
+    let word = quoter.quote(s);
+    if word.is_empty() { String::from("''") } else { word }
+}
+
+/// Returns the text of a word for an annotation, if it is printable non-ASCII text which quoting
+/// has turned into escapes.
+fn annotation(s: &Path, word: &str) -> Option<String> {
+    let text = str::from_utf8(s.as_os_str().as_bytes()).ok()?;
+    let hidden = !text.is_ascii() && word.is_ascii() && text.chars().all(is_printable);
+    hidden.then(|| String::from(text))
+}
+
+/// Quotes each argument for bash, as [`quotemeta`] does, writing empty arguments as `''` so that
+/// they are not lost.
+fn bash_words<I, S>(args: I) -> Vec<String>
+where
+    I: IntoIterator<Item = S>,
+    S: AsRef<Path>,
+{
+    args.into_iter().map(|arg| quoted_word(&Quoter::new(), arg)).collect()
+}
+
+/// Quotes each argument for bash and joins them with spaces. Empty arguments are written as `''`
+/// so that they are not lost.
+pub(crate) fn join_bash<I, S>(args: I) -> String
+where
+    I: IntoIterator<Item = S>,
+    S: AsRef<Path>,
+{
+    bash_words(args).join(" ")
+}
+
+/// Quotes each argument for the given shell and joins them with spaces, as [`join_bash`] does.
+pub(crate) fn join_quoted<I, S>(args: I, shell: Shell) -> Result<String, NotPortable>
+where
+    I: IntoIterator<Item = S>,
+    S: AsRef<Path>,
+{
+    let words = args.into_iter().map(|arg| {
+        let word = shell.quote(arg)?;
+        Ok(if word.is_empty() { String::from("''") } else { word })
+    });
+    Ok(words.collect::<Result<Vec<_>, NotPortable>>()?.join(" "))
+}
+
+/// Returns the canonical quoted form of a command line.
+///
+/// Each argument is quoted by [`quotemeta`](crate::quotemeta), with empty arguments written as
+/// `''`, and the arguments are separated by single spaces. The quoting is deterministic and the
+/// quoted arguments never contain an unquoted space, so two argument lists are equal exactly when
+/// their canonical forms are. This makes it suitable for logging, deduplicating and comparing
+/// commands, as well as pasting into bash.
+///
+/// ```
+/// use quotemeta::canonical_command;
+///
+/// assert_eq!(&canonical_command(["ls", "-l", "My Documents", ""]), "ls -l 'My Documents' ''");
+/// ```
+pub fn canonical_command<I, S>(args: I) -> String
+where
+    I: IntoIterator<Item = S>,
+    S: AsRef<Path>,
+{
+    join_bash(args)
+}
+
+/// Returns a command line with one quoted argument per line, joined by ` \` continuations.
+///
+/// The arguments are quoted as for [`canonical_command`], so the result is still a single command,
+/// but one which is friendly to line-oriented tools: changing one argument changes only its line
+/// in a diff. Since the quoting never puts a newline within a line, a continuation can never be
+/// mistaken for part of an argument. There is no trailing newline.
+///
+/// ```
+/// use quotemeta::quotemeta_args_lines;
+///
+/// assert_eq!(quotemeta_args_lines(["cp", "", "a b"]), "cp \\\n'' \\\n'a b'");
+/// ```
+pub fn quotemeta_args_lines<I, S>(args: I) -> String
+where
+    I: IntoIterator<Item = S>,
+    S: AsRef<Path>,
+{
+    bash_words(args).join(" \\\n")
+}
+
+/// Quotes each distinct item only once, returning the unique quoted strings in order of first
+/// appearance, and for each item, the index of its quoted form among them.
+///
+/// This pays off when the same values recur many times, such as a handful of directories each
+/// containing many files. The original sequence of quoted items is recovered by looking up each
+/// index in turn.
+///
+/// ```
+/// use quotemeta::quotemeta_dedup;
+///
+/// let (unique, indices) = quotemeta_dedup(["a b", "c", "a b"]);
+/// assert_eq!(unique, ["'a b'", "c"]);
+/// assert_eq!(indices, [0, 1, 0]);
+/// ```
+pub fn quotemeta_dedup<I, S>(items: I) -> (Vec<String>, Vec<usize>)
+where
+    I: IntoIterator<Item = S>,
+    S: AsRef<Path>,
+{
+    let (mut unique, mut indices, mut seen) = (Vec::new(), Vec::new(), HashMap::new());
+    for item in items {
+        let bytes = item.as_ref().as_os_str().as_bytes();
+        let index = if let Some(&index) = seen.get(bytes) {
+            index
+        } else {
+            let index = unique.len();
+            unique.push(quotemeta(&item));
+            let _ = seen.insert(bytes.to_vec(), index);
+            index
+        };
+        indices.push(index);
+    }
+    (unique, indices)
+}
+
+/// An opaque key which identifies a command line, for example to cache the results of running it.
+///
+/// Two keys are equal exactly when they were built from equal argument lists, since they hold the
+/// [`canonical_command`].
+///
+/// ```
+/// use quotemeta::CommandKey;
+/// use std::collections::HashMap;
+///
+/// let mut cache = HashMap::new();
+/// cache.insert(CommandKey::from_args(["uname", "-r"]), "6.1.0");
+/// let args = vec![String::from("uname"), String::from("-r")];
+/// assert_eq!(cache.get(&CommandKey::from_args(args)), Some(&"6.1.0"));
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct CommandKey(String);
+
+impl CommandKey {
+    /// Builds the key for the given command line.
+    pub fn from_args<I, S>(args: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<Path>,
+    {
+        Self(canonical_command(args))
+    }
+
+    /// Returns the canonical form of the command line, as given by [`canonical_command`].
+    #[must_use]
+    pub fn as_str(&self) -> &str { &self.0 }
+}
+
+/// A builder for a command line, with redirections.
+///
+/// The program and its arguments are quoted by the given [`Quoter`], and empty words are written
+/// as `''` so that they are not lost. The targets of redirections are quoted by the same quoter,
+/// except that its [`FirstBytePolicy`] is ignored: the shell never takes a redirection target as a
+/// command name or an option, so there is no call to guard against a leading `-` or `=`. Quoting
+/// is still needed there, since the shell expands the target, and a target which splits into
+/// several words is an error.
+///
+/// ```
+/// use quotemeta::{CommandLine, FirstBytePolicy, Quoter};
+///
+/// let quoter = Quoter::new().first_byte_policy(FirstBytePolicy::Conservative);
+/// let command = CommandLine::with_quoter("sort", quoter)
+///     .arg("-r")
+///     .redirect_in("-input")
+///     .redirect_out("sorted list");
+/// assert_eq!(command.to_string(), "sort '-r' < -input > 'sorted list'");
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct CommandLine {
+    quoter: Quoter,
+    redirect_quoter: Quoter,
+    words: Vec<String>,
+    redirects: Vec<String>,
+    annotations: Vec<String>,
+    annotate_unicode: bool,
+}
+
+impl CommandLine {
+    /// Starts a command line which runs `program`, quoted as by [`quotemeta`].
+    pub fn new(program: impl AsRef<Path>) -> Self { Self::with_quoter(program, Quoter::new()) }
+
+    /// Starts a command line which runs `program`, quoted by the given [`Quoter`].
+    pub fn with_quoter(program: impl AsRef<Path>, quoter: Quoter) -> Self {
+        let redirect_quoter = quoter.clone().first_byte_policy(FirstBytePolicy::Argument);
+        let mut command = Self {
+            quoter,
+            redirect_quoter,
+            words: Vec::new(),
+            redirects: Vec::new(),
+            annotations: Vec::new(),
+            annotate_unicode: false,
+        };
+        command.push_word(program.as_ref());
+        command
+    }
+
+    /// Sets whether to follow the command with a comment giving the text of any arguments or
+    /// targets which are non-ASCII UTF-8, and so have been quoted as escapes that are unreadable.
+    /// For example, `cat $'\360\237\246\200'  # 🦀`.
+    ///
+    /// The command itself is unchanged. Only printable text is copied into the comment, so it
+    /// cannot end the comment early, and nothing is written for text which the quoter has left
+    /// readable, or for bytes which are not valid UTF-8. The comment runs to the end of the line,
+    /// so nothing else should follow the command on it; and note that zsh does not recognise
+    /// comments at an interactive prompt unless the `interactive_comments` option is set.
+    ///
+    /// ```
+    /// use quotemeta::CommandLine;
+    ///
+    /// let command = CommandLine::new("cat").arg("\u{1f980}").annotate_unicode(true);
+    /// assert_eq!(command.to_string(), "cat $'\\360\\237\\246\\200'  # \u{1f980}");
+    /// ```
+    #[must_use]
+    pub const fn annotate_unicode(mut self, annotate: bool) -> Self {
+        self.annotate_unicode = annotate;
+        self
+    }
+
+    /// Quotes and appends a word, noting its text if it needs an annotation.
+    fn push_word(&mut self, s: &Path) {
+        let word = quoted_word(&self.quoter, s);
+        self.annotations.extend(annotation(s, &word));
+        self.words.push(word);
+    }
+
+    /// Quotes and appends a redirection with the given operator, noting the text of its target if
+    /// it needs an annotation.
+    fn push_redirect(&mut self, operator: &str, file: &Path) {
+        let target = quoted_word(&self.redirect_quoter, file);
+        self.annotations.extend(annotation(file, &target));
+        self.redirects.push(format!("{operator} {target}"));
+    }
+
+    /// Appends an argument.
+    #[must_use]
+    pub fn arg(mut self, arg: impl AsRef<Path>) -> Self {
+        self.push_word(arg.as_ref());
+        self
+    }
+
+    /// Appends several arguments.
+    #[must_use]
+    pub fn args<I, S>(mut self, args: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<Path>,
+    {
+        for arg in args {
+            self.push_word(arg.as_ref());
+        }
+        self
+    }
+
+    /// Redirects the standard input of the command from the given file, as `< file`.
+    #[must_use]
+    pub fn redirect_in(mut self, file: impl AsRef<Path>) -> Self {
+        self.push_redirect("<", file.as_ref());
+        self
+    }
+
+    /// Redirects the standard output of the command to the given file, as `> file`.
+    #[must_use]
+    pub fn redirect_out(mut self, file: impl AsRef<Path>) -> Self {
+        self.push_redirect(">", file.as_ref());
+        self
+    }
+}
+
+impl fmt::Display for CommandLine {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.words.join(" "))?;
+        self.redirects.iter().try_for_each(|redirect| write!(f, " {redirect}"))?;
+        if self.annotate_unicode && !self.annotations.is_empty() {
+            write!(f, "  # {}", self.annotations.join(" "))?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        canonical_command, is_printable, quotemeta, quotemeta_args_lines, quotemeta_dedup,
+        tests::shell_words, CommandKey, CommandLine, FirstBytePolicy, Quoter,
+    };
+    #[cfg(unix)] use std::os::unix::ffi::OsStrExt;
+    use std::{collections::HashMap, ffi::OsStr};
+
+    #[test]
+    fn test_canonical_command() {
+        assert_eq!(&canonical_command(Vec::<&str>::new()), "");
+        assert_eq!(&canonical_command([""]), "''");
+        assert_eq!(&canonical_command(["echo", "a b", "it's"]), r"echo 'a b' $'it\'s'");
+        assert_eq!(&canonical_command([OsStr::from_bytes(b"\xff")]), r"$'\377'");
+    }
+
+    #[test]
+    fn test_quotemeta_args_lines() {
+        assert_eq!(quotemeta_args_lines(Vec::<&str>::new()), "");
+        assert_eq!(quotemeta_args_lines(["ls"]), "ls");
+        let args: [&[u8]; 6] = [b"printf", b"%s\n", b"", b"a b", b"line\nbreak", b"\xff"];
+        let lines = quotemeta_args_lines(args.iter().map(|&arg| OsStr::from_bytes(arg)));
+        assert_eq!(lines.lines().count(), args.len());
+        for (line, &arg) in lines.lines().zip(&args) {
+            let word = line.strip_suffix(" \\").unwrap_or(line);
+            assert_eq!(shell_words(word), [arg]);
+        }
+        assert!(!lines.lines().last().expect("non-empty").ends_with('\\'));
+        // The shell removes each backslash-newline, leaving the arguments separated by spaces.
+        assert_eq!(shell_words(&lines.replace("\\\n", "")), args);
+    }
+
+    #[test]
+    fn test_quotemeta_dedup() {
+        let items = ["/srv/my dir", "/tmp", "", "/srv/my dir", "/tmp", "/srv/my dir", ""];
+        let (unique, indices) = quotemeta_dedup(items);
+        assert_eq!(unique, ["'/srv/my dir'", "/tmp", ""]);
+        assert_eq!(indices, [0, 1, 2, 0, 1, 0, 2]);
+        let rebuilt: Vec<&String> = indices.iter().filter_map(|&index| unique.get(index)).collect();
+        let expected: Vec<String> = items.iter().map(quotemeta).collect();
+        assert_eq!(rebuilt, expected.iter().collect::<Vec<_>>());
+
+        // Items are keyed by their bytes, which need not be valid UTF-8.
+        let invalid: [&[u8]; 3] = [b"\xff", b"\xfe", b"\xff"];
+        let bytes = invalid.iter().map(|&s| OsStr::from_bytes(s));
+        let quoted = vec![String::from(r"$'\377'"), String::from(r"$'\376'")];
+        assert_eq!(quotemeta_dedup(bytes), (quoted, vec![0, 1, 0]));
+        assert_eq!(quotemeta_dedup(Vec::<&str>::new()), (vec![], vec![]));
+    }
+
+    #[test]
+    fn test_command_key() {
+        let key = CommandKey::from_args(["grep", "-r", "a b"]);
+        assert_eq!(key, CommandKey::from_args(vec!["grep", "-r", "a b"]));
+        assert_eq!(key.as_str(), "grep -r 'a b'");
+        // Splitting or joining arguments, or dropping empty ones, gives a different key.
+        assert_ne!(key, CommandKey::from_args(["grep", "-r", "a", "b"]));
+        assert_ne!(key, CommandKey::from_args(["grep", "-r a b"]));
+        assert_ne!(CommandKey::from_args(["ls", ""]), CommandKey::from_args(["ls"]));
+        assert_ne!(CommandKey::from_args([""]), CommandKey::from_args(Vec::<&str>::new()));
+
+        let entries = vec![(key, 1), (CommandKey::from_args(["ls"]), 2)];
+        let cache: HashMap<_, _> = entries.into_iter().collect();
+        assert_eq!(cache.get(&CommandKey::from_args(["grep", "-r", "a b"])), Some(&1));
+        assert_eq!(cache.get(&CommandKey::from_args(["ls"])), Some(&2));
+        assert_eq!(cache.get(&CommandKey::from_args(["ls", "-l"])), None);
+    }
+
+    #[test]
+    fn test_command_line() {
+        let conservative = Quoter::new().first_byte_policy(FirstBytePolicy::Conservative);
+        let command = CommandLine::with_quoter("cat", conservative.clone())
+            .arg("-x")
+            .redirect_in("-x")
+            .redirect_out("my file");
+        assert_eq!(command.to_string(), "cat '-x' < -x > 'my file'");
+        let redirected =
+            CommandLine::with_quoter("cmd", conservative).redirect_out("-x").redirect_in("in put");
+        assert_eq!(redirected.to_string(), "cmd > -x < 'in put'");
+
+        let plain = CommandLine::new("printf").args(["%s\n", "", "a b"]).redirect_out("");
+        assert_eq!(plain.to_string(), r"printf $'%s\012' '' 'a b' > ''");
+        assert_eq!(CommandLine::new("true").to_string(), "true");
+        // The targets are still quoted as usual.
+        let odd = CommandLine::new("wc").redirect_in("*.txt").redirect_out("\n");
+        assert_eq!(odd.to_string(), r"wc < '*.txt' > $'\012'");
+    }
+
+    #[test]
+    fn test_annotate_unicode() {
+        let crab = CommandLine::new("cat").arg("\u{1f980}").annotate_unicode(true);
+        assert_eq!(crab.to_string(), "cat $'\\360\\237\\246\\200'  # \u{1f980}");
+        let line = crab.to_string();
+        let (command, comment) = line.split_once("  # ").expect("annotated");
+        assert_eq!(shell_words(command), [&b"cat"[..], "\u{1f980}".as_bytes()]);
+        assert_eq!(comment, "\u{1f980}");
+        // Everything is annotated in order, including the targets of redirections.
+        let several = CommandLine::new("cp")
+            .args(["caf\u{e9}", "plain", "a b"])
+            .redirect_out("\u{65e5}\u{672c}")
+            .annotate_unicode(true);
+        let quoted = r"cp $'caf\303\251' plain 'a b' > $'\346\227\245\346\234\254'";
+        assert_eq!(several.to_string(), format!("{quoted}  # caf\u{e9} \u{65e5}\u{672c}"));
+        assert_eq!(several.annotate_unicode(false).to_string(), quoted);
+        // Nothing is annotated without non-ASCII text, nor text which could break the comment.
+        let none = CommandLine::new("printf").args(["%s\n", "\u{1f980}\n", "a\u{202e}b"]);
+        assert!(!none.annotate_unicode(true).to_string().contains('#'));
+        let truncated = OsStr::from_bytes(b"\xf0\x9f\xa6");
+        let invalid = CommandLine::new("cat").arg(truncated).annotate_unicode(true);
+        assert_eq!(invalid.to_string(), r"cat $'\360\237\246'");
+        // Nor is text which the quoter has left readable.
+        let readable = Quoter::new().literal_utf8(is_printable);
+        let literal =
+            CommandLine::with_quoter("cat", readable).arg("\u{1f980}").annotate_unicode(true);
+        assert_eq!(literal.to_string(), "cat '\u{1f980}'");
+    }
+}
@@ -0,0 +1,76 @@
+//! Working out which shells can run a piece of quoted output.
+
+/// Returns the oldest version of bash which understands the quoting in the given shell word or
+/// command line, as `(major, minor)`, or `None` if it does not use any bash-specific quoting at
+/// all.
+///
+/// This scans for the features which the quoting in this crate may produce: `$'...'` (bash 2.0)
+/// and, within it, the `\u` and `\U` escapes (bash 4.2). It tracks single quotes, double quotes and
+/// backslashes so that a `$'` within other quoting is not counted, but does not otherwise try to
+/// parse the input, so arbitrary scripts may well use features it does not know about.
+///
+/// ```
+/// use quotemeta::{min_bash_version, quotemeta};
+///
+/// assert_eq!(min_bash_version(&quotemeta("a b")), None);
+/// assert_eq!(min_bash_version(&quotemeta("\n")), Some((2, 0)));
+/// assert_eq!(min_bash_version(r"$'caf\u00e9'"), Some((4, 2)));
+/// ```
+#[must_use]
+pub fn min_bash_version(s: &str) -> Option<(u32, u32)> {
+    let (mut version, mut chars) = (None, s.chars().peekable());
+    let mut require = |needed: (u32, u32)| version = version.max(Some(needed));
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => {
+                let _ = chars.next();
+            }
+            '\'' => chars.by_ref().take_while(|&quoted| quoted != '\'').for_each(drop),
+            '"' => {
+                while let Some(quoted) = chars.next() {
+                    match quoted {
+                        '"' => break,
+                        '\\' => {
+                            let _ = chars.next();
+                        }
+                        _ => {}
+                    }
+                }
+            }
+            '$' if chars.peek() == Some(&'\'') => {
+                let _ = chars.next();
+                require((2, 0));
+                while let Some(quoted) = chars.next() {
+                    match quoted {
+                        '\'' => break,
+                        '\\' => {
+                            if matches!(chars.next(), Some('u' | 'U')) {
+                                require((4, 2));
+                            }
+                        }
+                        _ => {}
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+    version
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{min_bash_version, quotemeta};
+
+    #[test]
+    fn test_min_bash_version() {
+        for s in &["", "plain", "'a b'", r"'it'\''s'", "'\u{e9}'", "\"$'\u{e9}'\"", r"\$'x'"] {
+            assert_eq!(min_bash_version(s), None, "{s}");
+        }
+        assert_eq!(min_bash_version(&quotemeta("\u{e9}\n")), Some((2, 0)));
+        assert_eq!(min_bash_version(r"$'\302\243'"), Some((2, 0)));
+        assert_eq!(min_bash_version(r"$'\\u00e9'"), Some((2, 0)));
+        assert_eq!(min_bash_version(r"$'\u00e9'"), Some((4, 2)));
+        assert_eq!(min_bash_version(r"echo 'a b' $'\012' $'\U0001f980'"), Some((4, 2)));
+    }
+}
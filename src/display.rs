@@ -0,0 +1,338 @@
+//! Human-readable renderings of filenames, which are _not_ suitable as shell input.
+
+use crate::{quoter::Quoter, Tier};
+#[cfg(unix)] use std::os::unix::ffi::OsStrExt;
+use std::path::Path;
+#[cfg(feature = "unicode-width")] use unicode_width::UnicodeWidthStr;
+
+/// Returns whether the character is easily confused with something else, or is invisible.
+const fn is_confusable(c: char) -> bool {
+    matches!(
+        c,
+        '\u{a0}'
+            | '\u{ad}'
+            | '\u{1680}'
+            | '\u{180e}'
+            | '\u{2000}' ..= '\u{200f}'
+            | '\u{2028}' ..= '\u{202f}'
+            | '\u{205f}' ..= '\u{2064}'
+            | '\u{2066}' ..= '\u{206f}'
+            | '\u{3000}'
+            | '\u{feff}'
+    )
+}
+
+/// Returns whether the character is printable.
+///
+/// That is, it is neither a control character nor one which is invisible or easily confused with
+/// something else, such as a zero-width space or a bidirectional override.
+///
+/// This is the policy used by [`quotemeta_display`], and makes a sensible predicate for
+/// [`Quoter::literal_utf8`].
+///
+/// ```
+/// use quotemeta::is_printable;
+///
+/// assert!(is_printable('\u{e9}'));
+/// assert!(!is_printable('\n'));
+/// assert!(!is_printable('\u{200b}'));
+/// ```
+#[must_use]
+pub fn is_printable(c: char) -> bool { !c.is_control() && !is_confusable(c) }
+
+/// Appends the readable form of the character.
+fn push_display_char(out: &mut String, c: char) {
+    match c {
+        '\0' => out.push_str(r"\0"),
+        '\u{7}' => out.push_str(r"\a"),
+        '\u{8}' => out.push_str(r"\b"),
+        '\t' => out.push_str(r"\t"),
+        '\n' => out.push_str(r"\n"),
+        '\u{b}' => out.push_str(r"\v"),
+        '\u{c}' => out.push_str(r"\f"),
+        '\r' => out.push_str(r"\r"),
+        '\u{1b}' => out.push_str(r"\e"),
+        '\\' => out.push_str(r"\\"),
+        _ if !is_printable(c) => out.extend(c.escape_unicode()),
+        _ => out.push(c),
+    }
+}
+
+#[cfg_attr(
+    feature = "clippy-insane",
+    expect(clippy::format_push_string, reason = "clearer than `write!` for one escape")
+)]
+fn display_bytes(s: &[u8]) -> String {
+    let mut out = String::with_capacity(s.len());
+    for chunk in s.utf8_chunks() {
+        for c in chunk.valid().chars() {
+            push_display_char(&mut out, c);
+        }
+        for &c in chunk.invalid() {
+            out.push_str(&format!(r"\x{c:02x}"));
+        }
+    }
+    out
+}
+
+/// Renders the given [`Path`] for a human to read.
+///
+/// Printable text (including UTF-8) is shown as-is, without any shell quoting. Control characters
+/// are shown as mnemonic escapes such as `\n` where one exists, and invisible or easily-confused
+/// characters such as zero-width spaces and bidirectional overrides are shown as `\u{...}`
+/// escapes. Bytes which are not valid UTF-8 are shown as `\xNN`, and backslashes are doubled so
+/// that the escapes are unambiguous.
+///
+/// The output is **not** shell input; use [`quotemeta`](crate::quotemeta) for that.
+///
+/// ```
+/// use quotemeta::quotemeta_display;
+///
+/// assert_eq!(&quotemeta_display("Hello, world"), "Hello, world");
+/// assert_eq!(&quotemeta_display("caf\u{e9}\n"), "caf\u{e9}\\n");
+/// assert_eq!(&quotemeta_display("zero\u{200b}width"), r"zero\u{200b}width");
+/// ```
+pub fn quotemeta_display(s: impl AsRef<Path>) -> String {
+    display_bytes(s.as_ref().as_os_str().as_bytes())
+}
+
+/// Renders the given [`Path`] for a human to read, showing the bytes of non-ASCII characters.
+///
+/// This is like [`quotemeta_display`], except that non-ASCII characters are shown as octal escapes
+/// of their UTF-8 encoding, as [`quotemeta`](crate::quotemeta) would, and so are bytes which are
+/// not valid UTF-8. Long runs of such escapes are hard to read, so the escapes for each character
+/// are kept together and `separator` is inserted between adjacent characters. Each invalid byte
+/// counts as a character of its own. A space or middle dot is legible on paper; a zero-width
+/// space suits a terminal, where it does not take up room.
+///
+/// The output is **not** shell input, not least because of the separators.
+///
+/// ```
+/// use quotemeta::quotemeta_display_grouped;
+///
+/// assert_eq!(&quotemeta_display_grouped("\u{a3}\u{a4}", " "), r"\302\243 \302\244");
+/// assert_eq!(&quotemeta_display_grouped("\u{a3}5", " "), r"\302\2435");
+/// ```
+#[cfg_attr(
+    feature = "clippy-insane",
+    expect(clippy::format_push_string, reason = "clearer than `write!` for one escape")
+)]
+pub fn quotemeta_display_grouped(s: impl AsRef<Path>, separator: &str) -> String {
+    let s = s.as_ref().as_os_str().as_bytes();
+    let mut out = String::with_capacity(s.len());
+    let mut after_group = false;
+    let units = s.utf8_chunks().flat_map(|chunk| {
+        chunk.valid().chars().map(Ok).chain(chunk.invalid().iter().map(|&c| Err(c)))
+    });
+    for unit in units {
+        let mut buf = [0; 4];
+        let group = match unit {
+            Ok(c) if c.is_ascii() => {
+                push_display_char(&mut out, c);
+                after_group = false;
+                continue;
+            }
+            Ok(c) => c.encode_utf8(&mut buf).as_bytes(),
+            Err(c) => &[c][..],
+        };
+        if after_group {
+            out.push_str(separator);
+        }
+        for &c in group {
+            out.push_str(&format!(r"\{c:03o}"));
+        }
+        after_group = true;
+    }
+    out
+}
+
+/// Returns both the shell-quoted and human-readable forms of the given [`Path`].
+///
+/// This is equivalent to `(quotemeta(s), quotemeta_display(s))`, but only classifies the input
+/// once and takes a shortcut when it does not need quoting at all.
+///
+/// ```
+/// use quotemeta::quotemeta_dual;
+///
+/// let (shell, display) = quotemeta_dual("\u{1f980} crab");
+/// assert_eq!(&shell, r"$'\360\237\246\200 crab'");
+/// assert_eq!(&display, "\u{1f980} crab");
+/// ```
+pub fn quotemeta_dual(s: impl AsRef<Path>) -> (String, String) {
+    let s = s.as_ref().as_os_str().as_bytes();
+    match Tier::of(s) {
+        Tier::Bare => {
+            let bare = Quoter::new().quote_bytes_as(s, Tier::Bare);
+            (bare.clone(), bare)
+        }
+        tier @ (Tier::Single | Tier::CQuoted) => {
+            (Quoter::new().quote_bytes_as(s, tier), display_bytes(s))
+        }
+    }
+}
+
+/// Renders the bytes as [`display_bytes`] does, but truncated to at most `width` characters.
+///
+/// If truncation is needed, the last character is an ellipsis, and escapes are never cut in half.
+#[cfg_attr(
+    feature = "clippy-insane",
+    expect(clippy::format_push_string, reason = "clearer than `write!` for one escape")
+)]
+fn display_bytes_within(s: &[u8], width: usize) -> String {
+    let (mut out, mut len, mut cut) = (String::with_capacity(s.len()), 0, None);
+    for chunk in s.utf8_chunks() {
+        let units = chunk.valid().chars().map(Ok).chain(chunk.invalid().iter().map(|&c| Err(c)));
+        for unit in units {
+            let start = out.len();
+            match unit {
+                Ok(c) => push_display_char(&mut out, c),
+                Err(c) => out.push_str(&format!(r"\x{c:02x}")),
+            }
+            len += out.get(start ..).map_or(0, |added| added.chars().count());
+            if len >= width && cut.is_none() {
+                cut = Some(start);
+            }
+            if len > width {
+                out.truncate(cut.unwrap_or(start));
+                out.push('\u{2026}');
+                return out;
+            }
+        }
+    }
+    out
+}
+
+/// Returns a label for the given [`Path`] to show in a table column `width` characters wide,
+/// along with its shell-quoted form.
+///
+/// The label is rendered as by [`quotemeta_display`] and, if it is too wide, is cut short and ends
+/// with an ellipsis. Escapes are never split, so the label may be a little narrower than `width`.
+/// Width is counted in characters, which is the number of terminal columns for most text but not
+/// for wide characters such as CJK ideographs. The quoted form is exactly what
+/// [`quotemeta`](crate::quotemeta) returns and is never truncated, so it suits a `--quote` option.
+///
+/// ```
+/// use quotemeta::quotemeta_table_cell;
+///
+/// let (label, machine) = quotemeta_table_cell("a rather long name.txt", 10);
+/// assert_eq!(&label, "a rather \u{2026}");
+/// assert_eq!(&machine, "'a rather long name.txt'");
+/// ```
+pub fn quotemeta_table_cell(s: impl AsRef<Path>, width: usize) -> (String, String) {
+    let s = s.as_ref().as_os_str().as_bytes();
+    let tier = Tier::of(s);
+    (display_bytes_within(s, width), Quoter::new().quote_bytes_as(s, tier))
+}
+
+/// Returns the number of terminal columns taken up by the given [`Path`] when quoted by the given
+/// [`Quoter`], for lining up columns of quoted names.
+///
+/// This is not the same as the length of the quoted form in bytes or characters once
+/// [`Quoter::literal_utf8`] lets through characters such as CJK ideographs, which take up two
+/// columns, or combining accents, which take up none. Escapes are ASCII, and so take up one
+/// column per character.
+///
+/// This requires the `unicode-width` feature.
+///
+/// ```
+/// use quotemeta::{is_printable, quoted_display_width, Quoter};
+///
+/// let quoter = Quoter::new().literal_utf8(is_printable);
+/// assert_eq!(quoter.quote("\u{65e5}\u{672c}"), "'\u{65e5}\u{672c}'");
+/// assert_eq!(quoted_display_width("\u{65e5}\u{672c}", &quoter), 6);
+/// ```
+#[cfg(feature = "unicode-width")]
+pub fn quoted_display_width(s: impl AsRef<Path>, quoter: &Quoter) -> usize {
+    UnicodeWidthStr::width(quoter.quote(s).as_str())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        quotemeta, quotemeta_display, quotemeta_display_grouped, quotemeta_dual,
+        quotemeta_table_cell,
+    };
+    #[cfg(unix)] use std::os::unix::ffi::OsStrExt;
+    use std::ffi::OsStr;
+
+    #[test]
+    fn test_quotemeta_display() {
+        assert_eq!(&quotemeta_display(""), "");
+        assert_eq!(&quotemeta_display("isn't"), "isn't");
+        assert_eq!(&quotemeta_display(r"a\b"), r"a\\b");
+        assert_eq!(&quotemeta_display("\t\r\n\0\u{1b}\u{7f}"), r"\t\r\n\0\e\u{7f}");
+        // C1 controls and confusables
+        assert_eq!(&quotemeta_display("\u{85}\u{a0}\u{202e}"), r"\u{85}\u{a0}\u{202e}");
+        // Printable UTF-8
+        assert_eq!(&quotemeta_display("\u{a3}5 \u{1f980}"), "\u{a3}5 \u{1f980}");
+        // Invalid UTF-8 (in this case, Latin-1.)
+        assert_eq!(&quotemeta_display(OsStr::from_bytes(b"\xa35")), r"\xa35");
+    }
+
+    #[test]
+    fn test_quotemeta_dual() {
+        for s in &["", "test", "a b", "\u{1f980}", "a b \u{1f980}", "tab\there", "\u{200b}"] {
+            assert_eq!(quotemeta_dual(s), (quotemeta(s), quotemeta_display(s)));
+        }
+        assert_eq!(
+            quotemeta_dual("hello \u{1f600}"),
+            (String::from(r"$'hello \360\237\230\200'"), String::from("hello \u{1f600}"))
+        );
+    }
+
+    #[test]
+    fn test_quotemeta_display_grouped() {
+        assert_eq!(&quotemeta_display_grouped("", "|"), "");
+        assert_eq!(&quotemeta_display_grouped("plain\n", "|"), r"plain\n");
+        // The separator goes at the boundary between the two characters, and nowhere else.
+        assert_eq!(&quotemeta_display_grouped("\u{a3}\u{20ac}", "|"), r"\302\243|\342\202\254");
+        assert_eq!(&quotemeta_display_grouped("a\u{a3}b\u{a3}", "|"), r"a\302\243b\302\243");
+        // Invalid bytes are groups of their own.
+        let mixed = OsStr::from_bytes(b"\xc2\xa3\xff\xfe");
+        let zwsp = "\u{200b}";
+        assert_eq!(
+            quotemeta_display_grouped(mixed, zwsp),
+            format!(r"\302\243{zwsp}\377{zwsp}\376")
+        );
+    }
+
+    #[test]
+    fn test_quotemeta_table_cell() {
+        let cell = |s: &str, width| quotemeta_table_cell(s, width);
+        assert_eq!(cell("short", 10), (String::from("short"), String::from("short")));
+        assert_eq!(cell("exactly10!", 10).0, "exactly10!");
+        let wide = "a very wide file name \u{1f980}.txt";
+        let (label, machine) = cell(wide, 12);
+        assert_eq!(label, "a very wide\u{2026}");
+        assert_eq!(machine, quotemeta(wide));
+        // Escapes are shown whole or not at all.
+        let (control_label, control_machine) = cell("bad\nname\there", 8);
+        assert_eq!(control_label, "bad\\nna\u{2026}");
+        assert_eq!(control_machine, r"$'bad\012name\011here'");
+        assert_eq!(cell("ab\ncd", 4).0, "ab\u{2026}");
+        assert_eq!(cell("ab\ncd", 10).0, r"ab\ncd");
+        assert_eq!(quotemeta_table_cell(OsStr::from_bytes(b"\xff\xfe"), 6).0, "\\xff\u{2026}");
+        assert_eq!(cell("anything", 0).0, "\u{2026}");
+    }
+
+    #[cfg(feature = "unicode-width")]
+    #[test]
+    fn test_quoted_display_width() {
+        use crate::{is_printable, quoted_display_width, Quoter};
+
+        let (plain, literal) = (Quoter::new(), Quoter::new().literal_utf8(is_printable));
+        assert_eq!(quoted_display_width("", &plain), 0);
+        assert_eq!(quoted_display_width("README.md", &plain), 9);
+        assert_eq!(quoted_display_width("my file", &plain), 9);
+        // Two columns for each ideograph, but none for a combining accent.
+        let cjk = "\u{65e5}\u{672c}\u{8a9e}.txt";
+        assert_eq!(literal.quote(cjk).chars().count(), 9);
+        assert_eq!(quoted_display_width(cjk, &literal), 12);
+        assert_eq!(quoted_display_width("e\u{301}", &literal), 3);
+        // Without literal UTF-8, the ideographs are escaped, three bytes each.
+        assert_eq!(quoted_display_width(cjk, &plain), "$'".len() + 9 * 4 + ".txt'".len());
+        // Escaped control characters take a column per character of the escape.
+        assert_eq!(&plain.quote("a\tb"), r"$'a\011b'");
+        assert_eq!(quoted_display_width("a\tb", &plain), 9);
+    }
+}
@@ -0,0 +1,95 @@
+//! Quoting for `echo -e`, which has backslash escapes of its own.
+
+use crate::{quotemeta, Tier};
+#[cfg(unix)] use std::os::unix::ffi::OsStrExt;
+use std::path::Path;
+
+/// Quotes the given [`Path`] as an argument to bash's `echo -e`, so that it prints the original
+/// bytes.
+///
+/// `echo -e` has its own backslash escapes, which differ slightly from those of `$'...'`: octal
+/// escapes are written `\0nnn`, with a leading zero, and `\c` stops the output altogether. This
+/// therefore writes the argument within single quotes, so that the shell passes the backslashes
+/// through untouched. Within the quotes, `\n`, `\t` and the other one-letter escapes which every
+/// `echo -e` understands are used where they exist, any other control character or non-ASCII byte
+/// is written as `\0nnn`, a backslash is doubled, and a single quote is written as `'\''`. A
+/// leading `-` is written as `\055`, so that `echo` does not take the argument as an option. An
+/// argument which needs none of this is left bare, as by [`quotemeta`], and an empty one is
+/// written as `''`.
+///
+/// The behaviour of `echo` varies between shells, and between bash's settings: `dash` has no `-e`
+/// and prints it, and `shopt -s xpg_echo` makes bash interpret escapes even without it. Prefer
+/// `printf '%s\n'` with an argument quoted by [`quotemeta`]; this is for working with scripts
+/// which already use `echo -e`. Note that `echo` adds a newline of its own.
+///
+/// ```
+/// use quotemeta::quotemeta_echo_e;
+///
+/// assert_eq!(quotemeta_echo_e("two\nlines"), r"'two\nlines'");
+/// assert_eq!(quotemeta_echo_e("-n"), r"'\055n'");
+/// ```
+#[cfg_attr(
+    feature = "clippy-insane",
+    expect(clippy::format_push_string, reason = "clearer than `write!` for one escape")
+)]
+pub fn quotemeta_echo_e(s: impl AsRef<Path>) -> String {
+    let bytes = s.as_ref().as_os_str().as_bytes();
+    if bytes.is_empty() {
+        return String::from("''");
+    }
+    if Tier::of(bytes) == Tier::Bare && !bytes.starts_with(b"-") {
+        return quotemeta(s);
+    }
+    let mut out = String::with_capacity(bytes.len() + 2);
+    out.push('\'');
+    for (offset, &c) in bytes.iter().enumerate() {
+        let escape = match c {
+            0x07 => "\\a",
+            0x08 => "\\b",
+            b'\t' => "\\t",
+            b'\n' => "\\n",
+            0x0b => "\\v",
+            0x0c => "\\f",
+            b'\r' => "\\r",
+            b'\\' => "\\\\",
+            b'\'' => "'\\''",
+            b'-' if offset == 0 => "\\055",
+            0 ..= 31 | 127 ..= 255 => {
+                out.push_str(&format!("\\0{c:03o}"));
+                continue;
+            }
+            _ => {
+                out.push(char::from(c));
+                continue;
+            }
+        };
+        out.push_str(escape);
+    }
+    out.push('\'');
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::quotemeta_echo_e;
+    #[cfg(unix)] use std::os::unix::ffi::OsStrExt;
+    use std::ffi::OsStr;
+
+    #[test]
+    fn test_quotemeta_echo_e() {
+        assert_eq!(&quotemeta_echo_e(""), "''");
+        assert_eq!(&quotemeta_echo_e("/bin/cat"), "/bin/cat");
+        assert_eq!(&quotemeta_echo_e("a b"), "'a b'");
+        assert_eq!(&quotemeta_echo_e("line\n"), r"'line\n'");
+        assert_eq!(&quotemeta_echo_e("\t\r\x07\x08\x0b\x0c"), r"'\t\r\a\b\v\f'");
+        assert_eq!(&quotemeta_echo_e(r"no \c here"), r"'no \\c here'");
+        assert_eq!(&quotemeta_echo_e("it's"), r"'it'\''s'");
+        // Octal escapes have a leading zero, and always three more digits.
+        assert_eq!(&quotemeta_echo_e(OsStr::from_bytes(b"\xff1")), r"'\03771'");
+        assert_eq!(&quotemeta_echo_e("\x1b[0m\x7f\0"), r"'\0033[0m\0177\0000'");
+        assert_eq!(&quotemeta_echo_e("caf\u{e9}"), r"'caf\0303\0251'");
+        // A leading dash is escaped so that it is not an option, but others are left alone.
+        assert_eq!(&quotemeta_echo_e("-e-"), r"'\055e-'");
+        assert_eq!(&quotemeta_echo_e("a-b"), "a-b");
+    }
+}
@@ -0,0 +1,506 @@
+//! Quoting for shell commands which are themselves embedded in some other file format.
+
+use crate::{
+    command::{join_bash, join_quoted},
+    quotemeta,
+    script::check_identifier,
+    InvalidIdentifier, NotPortable, Shell,
+};
+#[cfg(unix)] use std::os::unix::ffi::OsStrExt;
+use std::{ffi::OsStr, path::Path};
+
+/// Shell-quotes the given [`Path`] for use in the command field of a crontab entry.
+///
+/// cron runs the command with `/bin/sh`, but first splits it at the first unescaped `%` (which
+/// becomes a newline, with the rest of the line fed to the command's standard input), and turns
+/// `\%` into a literal `%`. This therefore applies [`quotemeta`] and then backslash-escapes every
+/// `%`. Any backslash already in the quoted form is part of a `\\`, `\'` or `\ooo` escape, so
+/// cron never mistakes it for the start of a `\%`.
+///
+/// ```
+/// use quotemeta::quotemeta_cron;
+///
+/// assert_eq!(&quotemeta_cron("/usr/bin/backup"), "/usr/bin/backup");
+/// assert_eq!(&quotemeta_cron("%Y-%m-%d"), r"'\%Y-\%m-\%d'");
+/// ```
+pub fn quotemeta_cron(s: impl AsRef<Path>) -> String { quotemeta(s).replace('%', r"\%") }
+
+/// Shell-quotes the given [`Path`] for use within a double-quoted Tcl string, such as the argument
+/// to Expect's `send` command.
+///
+/// There are two layers of quoting here: Tcl performs backslash, variable and command substitution
+/// within the `"..."` string, and the result is then parsed by the shell. This applies
+/// [`quotemeta`] and then backslash-escapes Tcl's metacharacters `[`, `]`, `$`, `{`, `}`, `"` and
+/// `\`.
+///
+/// Tcl's brace-quoting (`{...}`) would avoid the second layer of escaping, but the braces must be
+/// balanced and backslash-newline sequences are still processed, so it cannot represent arbitrary
+/// data. It is also awkward to append the `\r` which `send` usually needs.
+///
+/// ```
+/// use quotemeta::quotemeta_tcl;
+///
+/// let command = format!("send \"cat {}\\r\"", quotemeta_tcl("$HOME/my file"));
+/// assert_eq!(&command, r#"send "cat '\$HOME/my file'\r""#);
+/// ```
+pub fn quotemeta_tcl(s: impl AsRef<Path>) -> String {
+    let quoted = quotemeta(s);
+    let mut out = String::with_capacity(quoted.len());
+    for c in quoted.chars() {
+        if matches!(c, '[' | ']' | '$' | '{' | '}' | '"' | '\\') {
+            out.push('\\');
+        }
+        out.push(c);
+    }
+    out
+}
+
+/// Shell-quotes an `awk -v name=value` variable assignment, for use as the argument after `-v`.
+///
+/// `awk` processes escape sequences such as `\n` and `\\` in the value of a `-v` assignment, as if
+/// it were a string literal in the program, so the value needs escaping for `awk` before the whole
+/// `name=value` word is escaped for the shell. This doubles each backslash in the value and turns
+/// each newline into `\n`, and then applies [`quotemeta`]. A value of `C:\tmp` thus becomes
+/// `C:\\tmp` for `awk`, and the shell then sees `$'...\\\\...'`: two layers of escaping, each
+/// undone by a different program.
+///
+/// The name is not escaped for `awk`, so it must be an identifier, which `awk` spells the same way
+/// as the shell. Any other bytes are passed through, so the value need not be valid UTF-8.
+///
+/// # Errors
+///
+/// Returns [`InvalidIdentifier`] if `name` is not a valid variable name.
+///
+/// ```
+/// use quotemeta::quotemeta_awk_var;
+///
+/// assert_eq!(quotemeta_awk_var("dir", "/tmp").unwrap(), "dir=/tmp");
+/// assert_eq!(quotemeta_awk_var("dir", r"C:\tmp").unwrap(), r"$'dir=C:\\\\tmp'");
+/// assert!(quotemeta_awk_var("my dir", "/tmp").is_err());
+/// ```
+pub fn quotemeta_awk_var(name: &str, value: impl AsRef<Path>) -> Result<String, InvalidIdentifier> {
+    check_identifier(name)?;
+    let value = value.as_ref().as_os_str().as_bytes();
+    let mut assignment = Vec::with_capacity(name.len() + 1 + value.len());
+    assignment.extend_from_slice(name.as_bytes());
+    assignment.push(b'=');
+    for &c in value {
+        match c {
+            b'\\' => assignment.extend_from_slice(br"\\"),
+            b'\n' => assignment.extend_from_slice(br"\n"),
+            _ => assignment.push(c),
+        }
+    }
+    Ok(quotemeta(OsStr::from_bytes(&assignment)))
+}
+
+/// Shell-quotes the given [`Path`] for use within a `$(...)` command substitution.
+///
+/// bash parses the body of a command substitution properly, and so copes with the output of
+/// [`quotemeta`] there, but other shells and older versions of bash find the closing `)` by
+/// scanning for it, and so do many editors and other tools which highlight shell scripts. Some of
+/// those do not know about `$'...'`, and so take the `'` of an escaped `\'` within it as a closing
+/// quote, after which a `)` in the string ends the substitution early.
+///
+/// This therefore applies [`quotemeta`], and then escapes any `'` within `$'...'` in octal as
+/// `\047` instead. The guarantee is that every `'` in the result opens or closes a quoted string,
+/// so that any scanner which understands single quotes at all finds the same `)`; quoted bytes
+/// never include `'` itself, and the unquoted ones are all safe.
+///
+/// ```
+/// use quotemeta::{quotemeta, quotemeta_in_cmdsubst};
+///
+/// assert_eq!(&quotemeta("it's (sic)"), r"$'it\'s (sic)'");
+/// assert_eq!(&quotemeta_in_cmdsubst("it's (sic)"), r"$'it\047s (sic)'");
+/// ```
+pub fn quotemeta_in_cmdsubst(s: impl AsRef<Path>) -> String {
+    let plain = quotemeta(s);
+    let (mut out, mut chars) = (String::with_capacity(plain.len()), plain.chars());
+    let mut c_quoted = false;
+    while let Some(c) = chars.next() {
+        match c {
+            '$' if !c_quoted => {
+                // Outside quotes, a `$` can only start a `$'`.
+                out.push_str("$'");
+                let _ = chars.next();
+                c_quoted = true;
+            }
+            '\\' if c_quoted => match chars.next() {
+                Some('\'') => out.push_str(r"\047"),
+                escaped => {
+                    out.push('\\');
+                    out.extend(escaped);
+                }
+            },
+            '\'' if c_quoted => {
+                out.push('\'');
+                c_quoted = false;
+            }
+            '\'' => {
+                out.push('\'');
+                out.extend(chars.by_ref().take_while(|&quoted| quoted != '\''));
+                out.push('\'');
+            }
+            _ => out.push(c),
+        }
+    }
+    out
+}
+
+/// Quotes a command line for a Postgres `COPY ... FROM PROGRAM '...'` or `TO PROGRAM '...'`
+/// clause, ready to be put between the single quotes.
+///
+/// There are two layers of quoting here. Postgres runs the program with `/bin/sh`, so each
+/// argument is first quoted for [`Shell::Sh`] and the arguments are joined with spaces, with
+/// empty arguments written as `''`. The command line is then a SQL string literal, within which
+/// each single quote is doubled. A single quote in an argument therefore ends up as `''\''''`
+/// within the literal: the shell's `'\''` idiom, with each of its quotes doubled.
+///
+/// The result assumes that `standard_conforming_strings` is on, as it has been by default since
+/// Postgres 9.1, so that backslashes in the literal are taken literally. It must not be used
+/// within an `E'...'` literal.
+///
+/// # Errors
+///
+/// Returns [`NotPortable::InvalidUtf8`] if an argument is not valid UTF-8, since [`Shell::Sh`]
+/// cannot represent it.
+///
+/// ```
+/// use quotemeta::quotemeta_psql_program;
+///
+/// let program = quotemeta_psql_program(["gzip", "-dc", "/data/it's.csv.gz"]).unwrap();
+/// assert_eq!(&program, r"gzip -dc ''/data/it''\''''s.csv.gz''");
+/// let sql = format!("COPY sales FROM PROGRAM '{program}'");
+/// assert_eq!(&sql, r"COPY sales FROM PROGRAM 'gzip -dc ''/data/it''\''''s.csv.gz'''");
+/// ```
+pub fn quotemeta_psql_program<I, S>(args: I) -> Result<String, NotPortable>
+where
+    I: IntoIterator<Item = S>,
+    S: AsRef<Path>,
+{
+    Ok(join_quoted(args, Shell::Sh)?.replace('\'', "''"))
+}
+
+/// Quotes a command line for use as the handler in bash's `trap handler SIGNAL`, ready to be put
+/// in place of `handler`.
+///
+/// `trap` does not run its handler there and then, but stores it as a string, which bash parses
+/// and executes like `eval` each time the signal arrives. Anything in it which was not quoted at
+/// that point is expanded then, in whatever state the script is in. There are therefore two layers
+/// of quoting: each argument is quoted by [`quotemeta`], with empty arguments written as `''`,
+/// and the arguments are joined with spaces to make the command which runs when the trap fires;
+/// that is then single-quoted, with single quotes written as `'\''`, so that it reaches `trap`
+/// as-is. Since every argument is quoted, the handler expands nothing when it runs either.
+///
+/// With no arguments at all the handler is `''`, which tells bash to ignore the signal rather
+/// than do nothing when it arrives. Use `trap - SIGNAL` to restore the default instead.
+///
+/// ```
+/// use quotemeta::quotemeta_trap;
+///
+/// let handler = quotemeta_trap(["rm", "-f", "/tmp/my file"]);
+/// assert_eq!(&handler, r"'rm -f '\''/tmp/my file'\'''");
+/// assert_eq!(format!("trap {handler} EXIT"), r"trap 'rm -f '\''/tmp/my file'\''' EXIT");
+/// ```
+pub fn quotemeta_trap<I, S>(command_args: I) -> String
+where
+    I: IntoIterator<Item = S>,
+    S: AsRef<Path>,
+{
+    format!("'{}'", join_bash(command_args).replace('\'', r"'\''"))
+}
+
+/// Returns the length of the GNU `parallel` replacement string at the start of `s`, if any.
+fn parallel_token_len(s: &[u8]) -> Option<usize> {
+    let rest = s.strip_prefix(b"{")?;
+    let digits = rest.iter().take_while(|c| c.is_ascii_digit()).count();
+    let after = rest.get(digits ..)?;
+    let suffixes: &[&[u8]] = if digits == 0 {
+        &[b"}", b".}", b"/}", b"//}", b"/.}", b"#}", b"%}"]
+    } else {
+        &[b"}", b".}", b"/}", b"//}", b"/.}"]
+    };
+    let suffix = suffixes.iter().find(|suffix| after.starts_with(suffix))?;
+    Some(1 + digits + suffix.len())
+}
+
+/// Quotes one argument of a GNU `parallel` command template, as for [`quotemeta_parallel`].
+fn parallel_word(s: &[u8]) -> String {
+    if s.is_empty() {
+        return String::from("''");
+    }
+    let (mut out, mut start, mut offset) = (String::with_capacity(s.len()), 0, 0);
+    while let Some(rest) = s.get(offset ..).filter(|rest| !rest.is_empty()) {
+        if let Some(len) = parallel_token_len(rest) {
+            out.push_str(&quotemeta(OsStr::from_bytes(s.get(start .. offset).unwrap_or_default())));
+            out.push_str(&String::from_utf8_lossy(rest.get(.. len).unwrap_or_default()));
+            offset += len;
+            start = offset;
+        } else {
+            offset += 1;
+        }
+    }
+    out.push_str(&quotemeta(OsStr::from_bytes(s.get(start ..).unwrap_or_default())));
+    out
+}
+
+/// Quotes the given arguments as a command template for GNU `parallel`, leaving its replacement
+/// strings active.
+///
+/// `parallel` joins the words of its command with spaces and runs the result with a shell, after
+/// replacing each replacement string with the current input. The replacement strings are `{}`
+/// (the input), `{.}` (without its extension), `{/}` (its basename), `{//}` (its directory), `{/.}`
+/// (its basename without extension), `{#}` (the job number) and `{%}` (the job slot), as well as
+/// positional forms such as `{1}` and `{2/.}` for inputs with several columns. Each argument is
+/// quoted by [`quotemeta`], except that these are left bare, since `parallel` would otherwise
+/// replace them within the quotes. Text which merely looks like a replacement string, such as a
+/// literal `{}`, therefore cannot be passed on; use `parallel -I` to pick a different one.
+///
+/// `parallel` quotes the inputs it substitutes itself, so the inputs after `:::` (or on its
+/// standard input) should be passed as they are, not quoted by this crate as well. Nor should
+/// the result be used with `parallel -q`, which quotes the command template again. The shell
+/// must understand `$'...'`, as bash and zsh do.
+///
+/// ```
+/// use quotemeta::quotemeta_parallel;
+///
+/// let template = quotemeta_parallel(["convert", "{}", "-resize", "50%", "out dir/{/.}.png"]);
+/// assert_eq!(&template, "convert {} -resize '50%' 'out dir/'{/.}.png");
+/// ```
+pub fn quotemeta_parallel<I, S>(args: I) -> String
+where
+    I: IntoIterator<Item = S>,
+    S: AsRef<Path>,
+{
+    let words: Vec<String> =
+        args.into_iter().map(|arg| parallel_word(arg.as_ref().as_os_str().as_bytes())).collect();
+    words.join(" ")
+}
+
+/// Returns whether the character is reserved in the `Exec` key of a `.desktop` file, and so may
+/// only appear in a quoted argument.
+const fn is_desktop_reserved(c: char) -> bool {
+    c.is_ascii_control()
+        || matches!(
+            c,
+            ' ' | '"'
+                | '\''
+                | '\\'
+                | '>'
+                | '<'
+                | '~'
+                | '|'
+                | '&'
+                | ';'
+                | '$'
+                | '*'
+                | '?'
+                | '#'
+                | '('
+                | ')'
+                | '`'
+        )
+}
+
+/// Quotes the given [`Path`] as an argument in the `Exec` key of a freedesktop.org `.desktop`
+/// file.
+///
+/// The `Exec` key is not passed to a shell, but has a quoting syntax of its own. An argument
+/// containing any of the reserved characters (space, tab, newline, `"`, `'`, `\`, `>`, `<`, `~`,
+/// `|`, `&`, `;`, `$`, `*`, `?`, `#`, `(`, `)` and `` ` ``) must be enclosed in double quotes,
+/// within which `"`, `` ` ``, `$` and `\` are backslash-escaped. A `%` introduces a field code
+/// such as `%f` or `%u`, so a literal one must be doubled to `%%`, quoted or not.
+///
+/// The value of the key is then itself subject to the escaping of the key file format, which is
+/// applied first when the file is read. The result is written in that form, ready to be put after
+/// `Exec=`: backslashes are doubled again (so a literal backslash becomes `\\\\`), and newlines,
+/// tabs and carriage returns become `\n`, `\t` and `\r`. Desktop files are UTF-8, so any bytes
+/// which are not valid UTF-8 are replaced with U+FFFD.
+///
+/// ```
+/// use quotemeta::quotemeta_desktop;
+///
+/// let exec = format!("Exec=gimp {} %f", quotemeta_desktop("/opt/My Scripts/$1 off"));
+/// assert_eq!(&exec, r#"Exec=gimp "/opt/My Scripts/\\$1 off" %f"#);
+/// ```
+pub fn quotemeta_desktop(s: impl AsRef<Path>) -> String {
+    let text = String::from_utf8_lossy(s.as_ref().as_os_str().as_bytes());
+    let quote = text.is_empty() || text.contains(is_desktop_reserved);
+    let mut out = String::with_capacity(text.len() + 2);
+    if quote {
+        out.push('"');
+    }
+    for c in text.chars() {
+        match c {
+            '%' => out.push_str("%%"),
+            '\\' => out.push_str(r"\\\\"),
+            '"' | '`' | '$' => {
+                out.push_str(r"\\");
+                out.push(c);
+            }
+            '\n' => out.push_str(r"\n"),
+            '\t' => out.push_str(r"\t"),
+            '\r' => out.push_str(r"\r"),
+            _ => out.push(c),
+        }
+    }
+    if quote {
+        out.push('"');
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        quotemeta, quotemeta_awk_var, quotemeta_cron, quotemeta_desktop, quotemeta_in_cmdsubst,
+        quotemeta_parallel, quotemeta_psql_program, quotemeta_tcl, quotemeta_trap,
+        tests::shell_words, unquotemeta, InvalidIdentifier, NotPortable,
+    };
+    #[cfg(unix)] use std::os::unix::ffi::OsStrExt;
+    use std::ffi::OsStr;
+
+    #[test]
+    fn test_quotemeta_cron() {
+        assert_eq!(&quotemeta_cron(""), "");
+        assert_eq!(&quotemeta_cron("50%"), r"'50\%'");
+        assert_eq!(&quotemeta_cron("Hello, world"), "'Hello, world'");
+        assert_eq!(&quotemeta_cron("a space 100%"), r"'a space 100\%'");
+        assert_eq!(&quotemeta_cron("100%\n"), r"$'100\%\012'");
+        // A literal backslash before a `%` stays as a `\\` pair, so the `\%` escape is intact.
+        assert_eq!(&quotemeta_cron(r"\%"), r"$'\\\%'");
+    }
+
+    #[test]
+    fn test_quotemeta_tcl() {
+        assert_eq!(&quotemeta_tcl("plain"), "plain");
+        assert_eq!(&quotemeta_tcl("a b"), "'a b'");
+        assert_eq!(&quotemeta_tcl("$var"), r"'\$var'");
+        assert_eq!(&quotemeta_tcl("[exec rm]"), r"'\[exec rm\]'");
+        assert_eq!(&quotemeta_tcl("{a} \"b\""), r#"'\{a\} \"b\"'"#);
+        // Both layers of escaping are applied, so the shell sees `$'\012\''`.
+        assert_eq!(&quotemeta_tcl("\n'"), r"\$'\\012\\''");
+    }
+
+    #[test]
+    fn test_quotemeta_awk_var() {
+        let awk_var = |name, value: &str| quotemeta_awk_var(name, value).expect("valid name");
+        assert_eq!(&awk_var("n", ""), "n=");
+        assert_eq!(&awk_var("greeting", "hello"), "greeting=hello");
+        assert_eq!(&awk_var("greeting", "hello, world"), "'greeting=hello, world'");
+        // The shell turns `\\\\` into `\\`, and awk then turns that into `\`.
+        assert_eq!(&awk_var("path", r"a\b"), r"$'path=a\\\\b'");
+        // The newline reaches awk as `\n`, which needs no quoting for the shell.
+        assert_eq!(&awk_var("lines", "one\ntwo"), r"$'lines=one\\ntwo'");
+        assert_eq!(&awk_var("q", "it's $x"), r"$'q=it\'s $x'");
+        for name in ["", "a b", "1x", "a-b", "x=y", "caf\u{e9}"] {
+            assert_eq!(quotemeta_awk_var(name, "v"), Err(InvalidIdentifier(name.to_owned())));
+        }
+    }
+
+    /// Returns the offset of the `)` which closes a command substitution starting at the beginning
+    /// of `s`, found as a naive scanner would, knowing about single quotes but nothing else.
+    fn naive_cmdsubst_end(s: &str) -> Option<usize> {
+        let (mut depth, mut quoted): (usize, _) = (0, false);
+        for (offset, c) in s.char_indices() {
+            match c {
+                '\'' => quoted = !quoted,
+                '(' if !quoted => depth += 1,
+                ')' if !quoted => {
+                    depth -= 1;
+                    if depth == 0 {
+                        return Some(offset);
+                    }
+                }
+                _ => {}
+            }
+        }
+        None
+    }
+
+    #[test]
+    fn test_quotemeta_in_cmdsubst() {
+        let inputs: &[&[u8]] = &[
+            b"plain",
+            b"a (b) c",
+            b"it's)",
+            b"')'(",
+            b"\\')\\",
+            b"$(rm -rf /)",
+            b"\n)\xff'",
+            b"./it's (1).txt",
+        ];
+        for &s in inputs {
+            let s = OsStr::from_bytes(s);
+            let quoted = quotemeta_in_cmdsubst(s);
+            assert_eq!(unquotemeta(&quoted).expect("parses"), s);
+            let command = format!("$(echo {quoted})");
+            assert_eq!(naive_cmdsubst_end(&command), Some(command.len() - 1), "{command}");
+            let body = command.get(2 .. command.len() - 1).expect("in bounds");
+            assert_eq!(shell_words(body), [&b"echo"[..], s.as_bytes()]);
+        }
+        // The plain output confuses the naive scanner.
+        let command = format!("$(echo {})", quotemeta("it's)"));
+        assert_ne!(naive_cmdsubst_end(&command), Some(command.len() - 1));
+    }
+
+    #[test]
+    fn test_quotemeta_psql_program() {
+        let program = |args: &[&str]| quotemeta_psql_program(args).expect("valid UTF-8");
+        assert_eq!(&program(&["cat", "/tmp/data.csv"]), "cat /tmp/data.csv");
+        assert_eq!(&program(&["cat", "my data.csv"]), "cat ''my data.csv''");
+        assert_eq!(&program(&["cat", "it's.csv"]), r"cat ''it''\''''s.csv''");
+        assert_eq!(&program(&["printf", "%s", ""]), "printf ''%s'' ''''");
+        // Undoing the SQL layer gives a command line for `sh`.
+        let sh = program(&["grep", "it's here", r"C:\data"]);
+        assert_eq!(sh.replace("''", "'"), r"grep 'it'\''s here' 'C:\data'");
+        // `sh` has no `$'...'`, so bytes which are not UTF-8 cannot be passed.
+        let binary = [OsStr::new("cat"), OsStr::from_bytes(b"/tmp/\xff")];
+        assert_eq!(quotemeta_psql_program(binary), Err(NotPortable::InvalidUtf8(5)));
+    }
+
+    #[test]
+    fn test_quotemeta_trap() {
+        assert_eq!(&quotemeta_trap(["cleanup"]), "'cleanup'");
+        assert_eq!(&quotemeta_trap(Vec::<&str>::new()), "''");
+        let args: [&[u8]; 5] = [b"rm", b"-rf", b"/tmp/my dir", b"$HOME", b"it's\n"];
+        let handler = quotemeta_trap(args.iter().map(|&arg| OsStr::from_bytes(arg)));
+        // The first parse, of the `trap` command itself, gives the handler as one word.
+        let stored = unquotemeta(&handler).expect("parses").into_string().expect("UTF-8");
+        assert_eq!(stored, r"rm -rf '/tmp/my dir' '$HOME' $'it\'s\012'");
+        // The second, when the trap fires, gives the original arguments.
+        assert_eq!(shell_words(&stored), args);
+        assert_eq!(unquotemeta(&quotemeta_trap(["echo", ""])).expect("parses"), "echo ''");
+    }
+
+    #[test]
+    fn test_quotemeta_parallel() {
+        assert_eq!(&quotemeta_parallel(["gzip", "-9", "{}"]), "gzip -9 {}");
+        assert_eq!(&quotemeta_parallel(["cp", "{}", "/mnt/my backup/"]), "cp {} '/mnt/my backup/'");
+        assert_eq!(&quotemeta_parallel(["echo", ""]), "echo ''");
+        // Replacement strings stay outside the quotes, wherever they are in the argument.
+        assert_eq!(&quotemeta_parallel(["my {}.bak"]), "'my '{}.bak");
+        assert_eq!(&quotemeta_parallel(["{1} and {2//}"]), "{1}' and '{2//}");
+        assert_eq!(&quotemeta_parallel(["job {#} slot {%}"]), "'job '{#}' slot '{%}");
+        assert_eq!(&quotemeta_parallel(["{}{.}{/}{/.}"]), "{}{.}{/}{/.}");
+        // Anything else in braces is quoted as usual.
+        assert_eq!(&quotemeta_parallel(["{x}", "{1#}", "{", "}"]), "'{x}' '{1#}' '{' '}'");
+        assert_eq!(&quotemeta_parallel(["{{}}"]), "'{'{}'}'");
+        assert_eq!(&quotemeta_parallel([OsStr::from_bytes(b"\xff{}")]), r"$'\377'{}");
+    }
+
+    #[test]
+    fn test_quotemeta_desktop() {
+        assert_eq!(&quotemeta_desktop("/usr/bin/gimp"), "/usr/bin/gimp");
+        assert_eq!(&quotemeta_desktop(""), r#""""#);
+        assert_eq!(&quotemeta_desktop("my file"), r#""my file""#);
+        assert_eq!(&quotemeta_desktop("100%"), "100%%");
+        assert_eq!(&quotemeta_desktop("50% off"), r#""50%% off""#);
+        assert_eq!(&quotemeta_desktop("$HOME"), r#""\\$HOME""#);
+        assert_eq!(&quotemeta_desktop(r#"say "hi" `now`"#), r#""say \\"hi\\" \\`now\\`""#);
+        assert_eq!(&quotemeta_desktop(r"C:\dir"), r#""C:\\\\dir""#);
+        assert_eq!(&quotemeta_desktop("a\tb\nc"), r#""a\tb\nc""#);
+        assert_eq!(&quotemeta_desktop("~/it's"), r#""~/it's""#);
+        assert_eq!(&quotemeta_desktop("caf\u{e9}"), "caf\u{e9}");
+    }
+}
@@ -0,0 +1,106 @@
+//! Arguments for running a command directly, without a shell.
+
+#[cfg(unix)] use std::os::unix::ffi::OsStrExt;
+use std::{error::Error, ffi::OsString, fmt, path::Path};
+
+/// The error returned by [`quotemeta_noop_argv`] when an argument contains a NUL byte.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct NulInArgument {
+    index: usize,
+    offset: usize,
+}
+
+impl NulInArgument {
+    /// Returns the position of the offending argument in the list, counting from zero.
+    #[must_use]
+    pub const fn index(&self) -> usize { self.index }
+
+    /// Returns the offset of the first NUL byte within the argument.
+    #[must_use]
+    pub const fn offset(&self) -> usize { self.offset }
+}
+
+impl fmt::Display for NulInArgument {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "argument {} contains a NUL byte at offset {}, so cannot be passed to a program",
+            self.index, self.offset
+        )
+    }
+}
+
+impl Error for NulInArgument {}
+
+/// Collects the arguments, unquoted, for running a command directly rather than through a shell.
+///
+/// This does no quoting at all, and that is the point. A program started with `execve`, or with
+/// [`std::process::Command`], gets exactly the argument vector it is given, and nothing parses it
+/// along the way, so there is nothing to inject into. Shell-quoting arguments for that path is a
+/// bug: the program would see the quotes. If you can avoid running a shell, do so, and use this
+/// in place of [`quotemeta`](crate::quotemeta) to make that choice clear.
+///
+/// The arguments are kept as [`OsString`]s, so bytes which are not valid UTF-8 pass through
+/// intact. The only thing which cannot be passed is a NUL byte, since each argument is a C string
+/// which would end there; `Command` only finds that out when spawning the program, so it is
+/// checked here instead.
+///
+/// # Errors
+///
+/// Returns [`NulInArgument`] for the first argument which contains a NUL byte.
+///
+/// ```
+/// use quotemeta::quotemeta_noop_argv;
+/// use std::process::Command;
+///
+/// let argv = quotemeta_noop_argv(["ls", "-l", "my file; rm -rf /"]).unwrap();
+/// assert_eq!(argv, ["ls", "-l", "my file; rm -rf /"]);
+/// if let Some((program, args)) = argv.split_first() {
+///     let _command = Command::new(program).args(args);
+/// }
+/// ```
+pub fn quotemeta_noop_argv<I, S>(args: I) -> Result<Vec<OsString>, NulInArgument>
+where
+    I: IntoIterator<Item = S>,
+    S: AsRef<Path>,
+{
+    let mut out = Vec::new();
+    for (index, arg) in args.into_iter().enumerate() {
+        let arg = arg.as_ref().as_os_str();
+        if let Some(offset) = arg.as_bytes().iter().position(|&c| c == 0) {
+            return Err(NulInArgument { index, offset });
+        }
+        out.push(arg.to_owned());
+    }
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{quotemeta_noop_argv, NulInArgument};
+    #[cfg(unix)] use std::os::unix::ffi::OsStrExt;
+    use std::ffi::OsStr;
+
+    #[test]
+    fn test_quotemeta_noop_argv() {
+        let args = ["printf", "%s\n", "a b", "it's", "\"$HOME\"", "*", "", "caf\u{e9}"];
+        assert_eq!(quotemeta_noop_argv(args).expect("no NUL"), args);
+        let invalid = OsStr::from_bytes(b"\xff\xfe");
+        assert_eq!(quotemeta_noop_argv([invalid]).expect("no NUL"), [invalid]);
+        assert_eq!(quotemeta_noop_argv(Vec::<&str>::new()).expect("no NUL"), Vec::<&str>::new());
+    }
+
+    #[test]
+    fn test_quotemeta_noop_argv_nul() {
+        let err = quotemeta_noop_argv(["cat", "a\0b", "\0"]).expect_err("contains NUL");
+        assert_eq!((err.index(), err.offset()), (1, 1));
+        assert_eq!(
+            err.to_string(),
+            "argument 1 contains a NUL byte at offset 1, so cannot be passed to a program"
+        );
+        assert_eq!(
+            quotemeta_noop_argv(["\0"]).expect_err("contains NUL"),
+            NulInArgument { index: 0, offset: 0 }
+        );
+    }
+}
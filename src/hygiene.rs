@@ -0,0 +1,281 @@
+//! Helpers for tidying up filenames so that they do not need quoting at all.
+
+use crate::{is_safe, quotemeta, ByteClass};
+#[cfg(unix)] use std::os::unix::ffi::OsStrExt;
+use std::{ffi::OsStr, ops::Range};
+
+/// Suggests a rename of the given filename which does not need quoting.
+///
+/// Every byte which [`quotemeta`](crate::quotemeta) would need to quote or escape is replaced with
+/// `placeholder`. Since the replacement is bytewise, a multi-byte UTF-8 character becomes several
+/// placeholders; see [`suggest_safe_name_collapsed`] for one placeholder per run instead.
+///
+/// If `placeholder` would itself need quoting, `_` is used instead, so that the result never does.
+///
+/// ```
+/// use quotemeta::{needs_quoting, suggest_safe_name};
+///
+/// assert_eq!(suggest_safe_name(b"Hello, world!", b'_'), "Hello,_world_");
+/// assert!(!needs_quoting(suggest_safe_name(b"$HOME", b'_')));
+/// assert_eq!(suggest_safe_name(b"a b", b' '), "a_b");
+/// ```
+#[must_use]
+pub fn suggest_safe_name(s: &[u8], placeholder: u8) -> String {
+    replace_unsafe(s, placeholder, false)
+}
+
+/// Suggests a rename of the given filename which does not need quoting, as
+/// [`suggest_safe_name`] does but with each run of bytes which need quoting replaced by a single
+/// `placeholder`.
+///
+/// ```
+/// use quotemeta::suggest_safe_name_collapsed;
+///
+/// assert_eq!(suggest_safe_name_collapsed(b"a \t b", b'_'), "a_b");
+/// assert_eq!(suggest_safe_name_collapsed("\u{1f980}.rs".as_bytes(), b'-'), "-.rs");
+/// ```
+#[must_use]
+pub fn suggest_safe_name_collapsed(s: &[u8], placeholder: u8) -> String {
+    replace_unsafe(s, placeholder, true)
+}
+
+/// Replaces each byte which needs quoting, or each run of them if `collapse` is set, with
+/// `placeholder`, or with `_` if the placeholder is not safe either.
+fn replace_unsafe(s: &[u8], placeholder: u8, collapse: bool) -> String {
+    let placeholder = if is_safe(placeholder) { placeholder } else { b'_' };
+    let mut out = String::with_capacity(s.len());
+    let mut in_run = false;
+    for &c in s {
+        if is_safe(c) {
+            out.push(char::from(c));
+            in_run = false;
+        } else {
+            if !(collapse && in_run) {
+                out.push(char::from(placeholder));
+            }
+            in_run = true;
+        }
+    }
+    out
+}
+
+/// A change to part of a filename, as suggested by [`minimal_safe_edits`].
+///
+/// Each range is of byte offsets into the original filename.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum Edit {
+    /// Remove the bytes in the range.
+    Delete(Range<usize>),
+    /// Replace the bytes in the range with the single given byte.
+    Replace(Range<usize>, u8),
+}
+
+impl Edit {
+    /// Returns the range of bytes which the edit changes.
+    #[must_use]
+    pub fn range(&self) -> Range<usize> {
+        match self {
+            Self::Delete(range) | Self::Replace(range, _) => range.clone(),
+        }
+    }
+}
+
+/// Suggests the fewest edits to the given filename which would leave it not needing quoting, so
+/// that a rename can be shown as a diff rather than as a wholesale rewrite.
+///
+/// Each run of bytes which [`quotemeta`](crate::quotemeta) would need to quote or escape, as
+/// classified by [`ByteClass::of`], becomes a single edit. A run of nothing but control
+/// characters is deleted, since they are invisible anyway, and any other run is replaced with a
+/// single `_`, as [`suggest_safe_name_collapsed`] would. The exception is a filename which the
+/// deletions would leave empty, or as `.` or `..`, which name directories rather than files: its
+/// first run is replaced instead. The edits are in order and do not overlap. An empty filename
+/// gets no edits even though it does need quoting, since there is nothing to edit.
+///
+/// ```
+/// use quotemeta::{minimal_safe_edits, Edit};
+///
+/// assert_eq!(minimal_safe_edits(b"My Documents"), [Edit::Replace(2 .. 3, b'_')]);
+/// assert_eq!(minimal_safe_edits(b"notes\r\n.txt"), [Edit::Delete(5 .. 7)]);
+/// assert_eq!(minimal_safe_edits(b"notes.txt"), []);
+/// ```
+#[must_use]
+pub fn minimal_safe_edits(s: &[u8]) -> Vec<Edit> {
+    // Each run is its range and whether it is all control characters.
+    let mut runs: Vec<(Range<usize>, bool)> = Vec::new();
+    for (offset, &c) in s.iter().enumerate() {
+        let class = ByteClass::of(c);
+        if class == ByteClass::Safe {
+            continue;
+        }
+        match runs.last_mut() {
+            Some((range, controls)) if range.end == offset => {
+                range.end += 1;
+                *controls &= class == ByteClass::Control;
+            }
+            _ => runs.push((offset .. offset + 1, class == ByteClass::Control)),
+        }
+    }
+    let kept: Vec<u8> =
+        s.iter().copied().filter(|&c| ByteClass::of(c) == ByteClass::Safe).collect();
+    let replace_first = runs.iter().all(|&(_, controls)| controls)
+        && matches!(kept.as_slice(), b"" | b"." | b"..");
+    let edit = |(index, (range, controls)): (usize, (Range<usize>, bool))| {
+        if controls && !(index == 0 && replace_first) {
+            Edit::Delete(range)
+        } else {
+            Edit::Replace(range, b'_')
+        }
+    };
+    runs.into_iter().enumerate().map(edit).collect()
+}
+
+/// A byte order mark, as found by [`quotemeta_detect_bom`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum Bom {
+    /// The UTF-8 encoding of U+FEFF, `EF BB BF`.
+    Utf8,
+    /// U+FEFF in big-endian UTF-16, `FE FF`.
+    Utf16Be,
+    /// U+FEFF in little-endian UTF-16, `FF FE`.
+    Utf16Le,
+}
+
+impl Bom {
+    /// Returns the bytes of the byte order mark.
+    #[must_use]
+    pub const fn as_bytes(self) -> &'static [u8] {
+        match self {
+            Self::Utf8 => b"\xef\xbb\xbf",
+            Self::Utf16Be => b"\xfe\xff",
+            Self::Utf16Le => b"\xff\xfe",
+        }
+    }
+}
+
+/// Shell-quotes the given bytes as [`quotemeta`] does, and reports whether they start with a byte
+/// order mark.
+///
+/// A byte order mark at the start of a "filename" is usually an artifact of copying it out of a
+/// text file, and since it is invisible, the resulting error is baffling. The quoting escapes the
+/// mark along with any other bytes which need it, since they are all either non-ASCII or, in the
+/// case of `FF` and `FE`, not valid UTF-8 at all, but this lets the caller warn about it too.
+///
+/// ```
+/// use quotemeta::{quotemeta_detect_bom, Bom};
+///
+/// assert_eq!(quotemeta_detect_bom(b"\xef\xbb\xbfnotes.txt"), (
+///     String::from(r"$'\357\273\277notes.txt'"),
+///     Some(Bom::Utf8)
+/// ));
+/// assert_eq!(quotemeta_detect_bom(b"notes.txt"), (String::from("notes.txt"), None));
+/// ```
+#[must_use]
+pub fn quotemeta_detect_bom(s: &[u8]) -> (String, Option<Bom>) {
+    let boms = [Bom::Utf8, Bom::Utf16Be, Bom::Utf16Le];
+    let bom = boms.iter().copied().find(|bom| s.starts_with(bom.as_bytes()));
+    (quotemeta(OsStr::from_bytes(s)), bom)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        minimal_safe_edits, needs_quoting, quotemeta_detect_bom, suggest_safe_name,
+        suggest_safe_name_collapsed, Bom, Edit,
+    };
+    #[cfg(unix)] use std::os::unix::ffi::OsStrExt;
+    use std::ffi::OsStr;
+
+    #[test]
+    fn test_suggest_safe_name() {
+        assert_eq!(&suggest_safe_name(b"", b'_'), "");
+        assert_eq!(&suggest_safe_name(b"boring.txt", b'_'), "boring.txt");
+        // Spaces
+        assert_eq!(&suggest_safe_name(b"My  Documents", b'_'), "My__Documents");
+        assert_eq!(&suggest_safe_name_collapsed(b"My  Documents", b'_'), "My_Documents");
+        // Control characters
+        assert_eq!(&suggest_safe_name(b"a\nb\r\n", b'-'), "a-b--");
+        assert_eq!(&suggest_safe_name_collapsed(b"a\nb\r\n", b'-'), "a-b-");
+        // UTF-8
+        assert_eq!(&suggest_safe_name("\u{a3}5".as_bytes(), b'_'), "__5");
+        assert_eq!(&suggest_safe_name_collapsed("\u{1f980}.rs".as_bytes(), b'_'), "_.rs");
+
+        for input in [&b"it's"[..], b"\x00\xff", b"a b", "caf\u{e9}".as_bytes()] {
+            assert!(!needs_quoting(suggest_safe_name(input, b'_')));
+            assert!(!needs_quoting(suggest_safe_name_collapsed(input, b'_')));
+        }
+        // A placeholder which needs quoting gives way to `_`.
+        assert_eq!(&suggest_safe_name(b"a b", b' '), "a_b");
+        assert_eq!(&suggest_safe_name_collapsed(b"a  b", b'$'), "a_b");
+        assert_eq!(&suggest_safe_name(b"\xff", b'\xff'), "_");
+        assert_eq!(&suggest_safe_name(b"fine", b'$'), "fine");
+    }
+
+    /// Applies the edits suggested by [`minimal_safe_edits`].
+    fn apply_edits(s: &[u8], edits: &[Edit]) -> Vec<u8> {
+        let mut out = Vec::new();
+        for (offset, &c) in s.iter().enumerate() {
+            match edits.iter().find(|edit| edit.range().contains(&offset)) {
+                None => out.push(c),
+                Some(Edit::Replace(range, with)) if range.start == offset => out.push(*with),
+                Some(_) => {}
+            }
+        }
+        out
+    }
+
+    #[test]
+    fn test_minimal_safe_edits() {
+        assert_eq!(minimal_safe_edits(b""), []);
+        assert_eq!(minimal_safe_edits(b"boring-file_v2.txt"), []);
+        assert_eq!(minimal_safe_edits(b"a b"), [Edit::Replace(1 .. 2, b'_')]);
+        assert_eq!(minimal_safe_edits(b"a\x01\x02\x1b\x7fb"), [Edit::Delete(1 .. 5)]);
+        assert_eq!(minimal_safe_edits(b"\r\n"), [Edit::Replace(0 .. 2, b'_')]);
+        // Deleting everything would leave `.` or `..`.
+        assert_eq!(minimal_safe_edits(b"\x01.\x02"), [
+            Edit::Replace(0 .. 1, b'_'),
+            Edit::Delete(2 .. 3),
+        ]);
+        assert_eq!(minimal_safe_edits(b"\x01.."), [Edit::Replace(0 .. 1, b'_')]);
+        assert_eq!(minimal_safe_edits(b".\x01.\x02"), [
+            Edit::Replace(1 .. 2, b'_'),
+            Edit::Delete(3 .. 4),
+        ]);
+        let three_dots = minimal_safe_edits(b"\x01...\x02");
+        assert_eq!(three_dots, [Edit::Delete(0 .. 1), Edit::Delete(4 .. 5)]);
+        assert_eq!(minimal_safe_edits(b"a\t b"), [Edit::Replace(1 .. 3, b'_')]);
+        assert_eq!(
+            minimal_safe_edits("it's caf\u{e9}\n".as_bytes()),
+            [Edit::Replace(2 .. 3, b'_'), Edit::Replace(4 .. 5, b'_'), Edit::Replace(8 .. 11, b'_')]
+        );
+        assert_eq!(Edit::Delete(3 .. 5).range(), 3 .. 5);
+
+        let inputs: &[&[u8]] = &[
+            b"My  Documents", b"\x00x\xff", b"$HOME", b"a\nb\r\n", b"\x1b", b"\x01.\x02", b"\x01..",
+        ];
+        for &input in inputs {
+            let edited = apply_edits(input, &minimal_safe_edits(input));
+            assert!(!needs_quoting(OsStr::from_bytes(&edited)), "{:?}", input);
+            assert!(!matches!(edited.as_slice(), b"" | b"." | b".."), "{:?}", input);
+        }
+    }
+
+    #[test]
+    fn test_quotemeta_detect_bom() {
+        let expect = |quoted: &str, bom| (String::from(quoted), bom);
+        assert_eq!(quotemeta_detect_bom(b""), expect("", None));
+        assert_eq!(quotemeta_detect_bom(b"plain"), expect("plain", None));
+        let quoted = r"$'\357\273\277'";
+        assert_eq!(quotemeta_detect_bom(b"\xef\xbb\xbf"), expect(quoted, Some(Bom::Utf8)));
+        assert_eq!(
+            quotemeta_detect_bom(b"\xef\xbb\xbfa b"),
+            expect(r"$'\357\273\277a b'", Some(Bom::Utf8))
+        );
+        assert_eq!(quotemeta_detect_bom(b"\xfe\xffx"), expect(r"$'\376\377x'", Some(Bom::Utf16Be)));
+        assert_eq!(quotemeta_detect_bom(b"\xff\xfex"), expect(r"$'\377\376x'", Some(Bom::Utf16Le)));
+        // Only a leading mark counts, and only a whole one.
+        assert_eq!(quotemeta_detect_bom(b"a\xef\xbb\xbf").1, None);
+        assert_eq!(quotemeta_detect_bom(b"\xef\xbbx").1, None);
+    }
+}
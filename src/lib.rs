@@ -1,6 +1,6 @@
 //! Shell-quoting, à la Perl's `quotemeta` function.
 //!
-//! This crate currently provides a single [`quotemeta`] function which shell-escapes a filename or
+//! This crate's main entry point is the [`quotemeta`] function which shell-escapes a filename or
 //! other data. It is anticipated that it may expand to include fine-tuning of the escaping
 //! strategy, but for now it will return the input as-is if there are no troublesome characters,
 //! otherwise single-quoted if it is printable ASCII without single-quotes, otherwise it'll break
@@ -8,6 +8,9 @@
 //! Quoted"](https://www.gnu.org/software/bash/manual/html_node/ANSI_002dC-Quoting.html#ANSI_002dC-Quoting)
 //! for input which contains control codes or UTF-8 text.
 //!
+//! There are also a few helpers built on the same classification of bytes, such as
+//! [`needs_quoting`] and [`suggest_safe_name`], and [`unquotemeta`] to reverse the quoting.
+//!
 
 //// -- start of boilerplate that's generally pasted into the top of new projects -- ////
 #![cfg_attr(feature="clippy-insane", warn(
@@ -17,7 +20,8 @@
     // box_pointers, //// obsolete
     deprecated_in_future,
     // elided_lifetimes_in_paths,  //// suggests adding dubious <'_> noise everywhere
-    explicit_outlives_requirements, indirect_structural_match, keyword_idents,
+    explicit_outlives_requirements, keyword_idents,
+    // indirect_structural_match, //// obsolete; now a hard error
     macro_use_extern_crate, meta_variable_misuse,
     missing_copy_implementations,  //// too noisy; enable and inspect before release
     missing_debug_implementations, //// too noisy; enable and inspect before release
@@ -47,12 +51,30 @@
     // clippy::pub_enum_variant_names,
     // //// from clippy::restriction:
     clippy::implicit_return,    //// bad style
-    // clippy::integer_arithmetic, clippy::integer_division, //// uh-huh
+    clippy::arithmetic_side_effects, clippy::integer_division, //// uh-huh
     // clippy::float_arithmetic,
     clippy::missing_docs_in_private_items, //// too noisy; enable and inspect before release
     clippy::missing_inline_in_public_items, //// just moans about all public items
+    clippy::blanket_clippy_restriction_lints, //// yes, we really do want them all
+    clippy::allow_attributes_without_reason,  //// the `////` comments are the reasons
+    clippy::four_forward_slashes,             //// that's how this boilerplate is commented
+    clippy::min_ident_chars,                  //// `s` and `c` are fine names for strings and bytes
+    clippy::single_call_fn,                   //// small helper functions are good style
+    clippy::impl_trait_in_params,             //// `impl AsRef<Path>` is the public API
+    clippy::unused_trait_names,               //// e.g. `use OsStrExt as _` is just noise
+    clippy::shadow_reuse,                     //// e.g. `let foo = bar(foo)`
+    clippy::pub_use,                          //// the public API is flat; modules are private
+    clippy::arbitrary_source_item_ordering,   //// related items are kept together instead
+    clippy::redundant_test_prefix,            //// tests are named `test_foo` by convention
+    clippy::let_underscore_untyped,           //// `let _ = foo()` is a clear enough discard
+    clippy::std_instead_of_alloc, clippy::std_instead_of_core, //// we're not no_std
+    clippy::pattern_type_mismatch,            //// match ergonomics are fine, actually
+    clippy::pub_with_shorthand,               //// `pub(crate)` is the usual spelling
+    clippy::redundant_pub_crate,              //// fights with `unreachable_pub`
+    clippy::question_mark_used,               //// `?` is perfectly idiomatic
+    clippy::missing_trait_methods,            //// the provided methods are provided for a reason
+    clippy::use_debug,                        //// `{:?}` is a fine way to quote a string in a message
     // // clippy::multiple_inherent_impl,      //// breaks with e.g. derive macros
-    // clippy::shadow_reuse,                //// e.g. `let foo = bar(foo)`
     // clippy::shadow_same,                 //// e.g. `let foo = &foo`
     // clippy::mem_forget,                  //// triggered by no_panic macro
     // clippy::non_ascii_literal,
@@ -64,17 +86,89 @@
 //// #[no_panic] generates code which triggers clippy::mem_forget
 //#![cfg_attr(all(feature = "clippy-insane", feature = "no-panic"), allow(clippy::mem_forget))]
 //// -- end of boilerplate that's generally pasted into the top of new projects -- ////
+#![cfg_attr(all(test, feature = "clippy-insane"), allow(
+    clippy::default_numeric_fallback, //// test data is clearer without type suffixes
+    clippy::expect_used, clippy::panic, //// tests assert with .expect() and panic!()
+))]
 
 #[cfg(unix)] use std::os::unix::ffi::OsStrExt;
-use std::path::Path;
+use std::{borrow::Cow, convert::TryFrom, ffi::OsStr, fmt, io, path::Path, str};
 
-fn quotemeta_inner(s: &[u8]) -> String {
-    let (mut single_quoted, mut c_quoted) = (false, false);
-    let s = s
-        .iter()
-        .map(|&c| match c {
-            // These characters are safe to use without quoting or escaping.
-            b'+'
+mod classes;
+mod command;
+mod compat;
+mod display;
+mod echo;
+mod embed;
+mod exec;
+mod hygiene;
+mod make;
+mod outcome;
+mod pattern;
+mod printf;
+mod quoted;
+mod quoter;
+mod read;
+mod record;
+mod script;
+mod shellescape;
+mod template;
+mod unquote;
+mod windows;
+mod xargs;
+
+pub use crate::{
+    classes::{byte_classes_present, ByteClass, ByteClassSet},
+    command::{canonical_command, quotemeta_args_lines, quotemeta_dedup, CommandKey, CommandLine},
+    compat::min_bash_version,
+    display::{
+        is_printable, quotemeta_display, quotemeta_display_grouped, quotemeta_dual,
+        quotemeta_table_cell,
+    },
+    echo::quotemeta_echo_e,
+    embed::{
+        quotemeta_awk_var, quotemeta_cron, quotemeta_desktop, quotemeta_in_cmdsubst,
+        quotemeta_parallel, quotemeta_psql_program, quotemeta_tcl, quotemeta_trap,
+    },
+    exec::{quotemeta_noop_argv, NulInArgument},
+    hygiene::{
+        minimal_safe_edits, quotemeta_detect_bom, suggest_safe_name, suggest_safe_name_collapsed,
+        Bom, Edit,
+    },
+    make::{makefile_rule, quotemeta_make, InvalidMakeName},
+    outcome::{
+        output_to_input_offset, quote_arg, quotemeta_outcome, quotemeta_traced, QuoteOutcome,
+        QuotedArg, Transform, UnrepresentableReason,
+    },
+    pattern::{is_safe_as_pattern, quotemeta_pattern},
+    printf::quotemeta_printf_q,
+    quoted::Quoted,
+    quoter::{EscapeFormat, FirstBytePolicy, NulPolicy, QuoteStyle, Quoter, SpecError},
+    read::{quotemeta_read_line, NotReadable},
+    record::{quotemeta_record, read_record},
+    script::{
+        quotemeta_arith, quotemeta_ash, quotemeta_assign, quotemeta_bash_array, quotemeta_bash_zsh,
+        quotemeta_dotenv, quotemeta_portable_first, quotemeta_var_q, reproduction_script,
+        DotenvError, InvalidIdentifier, NonNumeric, NotPortable, Shell, ShellRequirement,
+    },
+    shellescape::quotemeta_go_shellescape,
+    template::{quotemeta_fill, quotemeta_keep_vars, TemplateError},
+    unquote::{normalize_quoted, quote_unquote_path, unquotemeta, unquotemeta_lenient, UnquoteError},
+    windows::{
+        quotemeta_windows, sanitize_windows_name, windows_safe_name, WindowsNameIssue,
+        WindowsRules,
+    },
+    xargs::quotemeta_xargs,
+};
+#[cfg(feature = "unicode-normalization")] pub use crate::quoter::Normalization;
+#[cfg(feature = "regex")] pub use crate::outcome::{quotemeta_require_match, PolicyViolation};
+#[cfg(feature = "unicode-width")] pub use crate::display::quoted_display_width;
+
+/// Returns whether the byte is safe to use without quoting or escaping.
+const fn is_safe(c: u8) -> bool {
+    matches!(
+        c,
+        b'+'
             | b','
             | b'-'
             | b'.'
@@ -85,32 +179,169 @@ fn quotemeta_inner(s: &[u8]) -> String {
             | b'@'
             | b'A' ..= b'Z'
             | b'_'
-            | b'a' ..= b'z' => char::from(c).to_string(),
+            | b'a' ..= b'z'
+    )
+}
+
+/// The amount of quoting required by a byte or string, from least to most.
+///
+/// The tiers are ordered, so that the tier required by a string is the greatest of those required
+/// by its bytes, and a [`Quoter`] can be told the least tier to use with [`Quoter::min_tier`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(
+    feature = "clippy-insane",
+    expect(clippy::exhaustive_enums, reason = "quotemeta only ever emits these three forms")
+)]
+pub enum Tier {
+    /// Used as-is.
+    Bare,
+    /// Single-quoted.
+    Single,
+    /// ANSI-C quoted.
+    CQuoted,
+}
+
+impl Tier {
+    /// Returns the tier required by a single byte.
+    const fn of_byte(c: u8) -> Self {
+        match c {
+            // These characters are safe to use without quoting or escaping.
+            _ if is_safe(c) => Self::Bare,
             // Control and high-bit-set characters require C-quoting and \ooo-escaping.
-            0 ..= 31 | 127 ..= 255 => {
-                c_quoted = true;
-                format!(r"\{:03o}", c)
-            }
+            //
             // A single quote or backslash must be C-quoted and backslash-escaped. Technically, we
             // can get away with just single-quoting backslashes, but they then must _not_ be
             // backslash-escaped. Since we don't know if a subsequent character might need to be
             // C-quoted, we play it safe.
-            b'\'' | b'\\' => {
-                c_quoted = true;
-                format!(r"\{}", char::from(c))
-            }
+            0 ..= 31 | 127 ..= 255 | b'\'' | b'\\' => Self::CQuoted,
             // Other characters are safe provided they are at least single-quoted.
-            _ => {
-                single_quoted = true;
-                char::from(c).to_string()
+            _ => Self::Single,
+        }
+    }
+
+    /// Returns the tier required by a string, which is that of its most demanding byte.
+    fn of(s: &[u8]) -> Self {
+        s.iter().map(|&c| Self::of_byte(c)).max().unwrap_or(Self::Bare)
+    }
+}
+
+/// Writes a byte to the body of an ANSI-C quoted string, escaping it if necessary.
+fn write_c_escaped(out: &mut impl fmt::Write, c: u8) -> fmt::Result {
+    match c {
+        0 ..= 31 | 127 ..= 255 => {
+            out.write_char('\\')?;
+            for digit in [c >> 6, (c >> 3) & 7, c & 7] {
+                out.write_char(char::from(b'0' + digit))?;
             }
-        })
-        .collect();
-    match (c_quoted, single_quoted) {
-        (true, _) => format!("$'{}'", s),
-        (false, true) => format!("'{}'", s),
-        (false, false) => s,
+            Ok(())
+        }
+        b'\'' | b'\\' => {
+            out.write_char('\\')?;
+            out.write_char(char::from(c))
+        }
+        _ => out.write_char(char::from(c)),
+    }
+}
+
+/// Returns the byte as it is written in the body of an ANSI-C quoted string.
+#[cfg_attr(
+    feature = "clippy-insane",
+    expect(clippy::expect_used, reason = "writing to a `String` cannot fail")
+)]
+pub(crate) fn c_escaped(c: u8) -> String {
+    let mut out = String::with_capacity(4);
+    write_c_escaped(&mut out, c).expect("writing to a String cannot fail");
+    out
+}
+
+/// Returns whether [`quotemeta`] would need to quote or escape the given [`Path`].
+///
+/// ```
+/// use quotemeta::needs_quoting;
+///
+/// assert!(!needs_quoting("/bin/cat"));
+/// assert!(needs_quoting("Hello, world"));
+/// ```
+pub fn needs_quoting(s: impl AsRef<Path>) -> bool {
+    !s.as_ref().as_os_str().as_bytes().iter().all(|&c| is_safe(c))
+}
+
+/// Returns the offset of the first byte which [`quotemeta`] would need to quote or escape, along
+/// with the [`Tier`] of quoting which that byte requires, or `None` if there is no such byte.
+///
+/// This is finer-grained than [`needs_quoting`], for example to highlight the trouble in an
+/// editor. A later byte may of course need more quoting than the first.
+///
+/// ```
+/// use quotemeta::{first_trouble, Tier};
+///
+/// assert_eq!(first_trouble(b"my file\n"), Some((2, Tier::Single)));
+/// assert_eq!(first_trouble(b"/bin/cat"), None);
+/// ```
+#[must_use]
+pub fn first_trouble(s: &[u8]) -> Option<(usize, Tier)> {
+    let (offset, &c) = s.iter().enumerate().find(|&(_, &c)| !is_safe(c))?;
+    Some((offset, Tier::of_byte(c)))
+}
+
+/// Returns whether the given [`Path`] is valid UTF-8, judging by the same bytes which [`quotemeta`]
+/// would quote.
+///
+/// Only valid UTF-8 can be written as-is by [`Quoter::literal_utf8`], so this can help decide
+/// whether that is worth trying.
+///
+/// ```
+/// use quotemeta::input_is_valid_utf8;
+///
+/// assert!(input_is_valid_utf8("caf\u{e9}"));
+/// ```
+pub fn input_is_valid_utf8(s: impl AsRef<Path>) -> bool {
+    str::from_utf8(s.as_ref().as_os_str().as_bytes()).is_ok()
+}
+
+/// Returns a compact summary of how [`quotemeta`] classifies the given bytes, for tools which
+/// store it to compare later without scanning the input again.
+///
+/// The digest is eight bytes:
+///
+/// * byte 0 is the [`Tier`] required by the whole input: 0 for [`Tier::Bare`], 1 for
+///   [`Tier::Single`] and 2 for [`Tier::CQuoted`];
+/// * bytes 1 and 2 are the number of bytes which need single-quoting, and bytes 3 and 4 the number
+///   which need C-quoting, each big-endian and saturating at 65535;
+/// * bytes 5 to 7 are the length of the input, big-endian and saturating at 16777215.
+///
+/// Equal inputs always have equal digests, and inputs below 16 MiB of different tiers or lengths
+/// always have different digests, but different inputs may of course have the same digest.
+///
+/// ```
+/// use quotemeta::classification_digest;
+///
+/// assert_eq!(classification_digest(b"a b\n"), [2, 0, 1, 0, 1, 0, 0, 4]);
+/// ```
+#[must_use]
+#[cfg_attr(
+    feature = "clippy-insane",
+    expect(clippy::big_endian_bytes, reason = "network byte order")
+)]
+pub fn classification_digest(s: &[u8]) -> [u8; 8] {
+    let (mut single, mut c_quoted): (usize, usize) = (0, 0);
+    for &c in s {
+        match Tier::of_byte(c) {
+            Tier::Bare => {}
+            Tier::Single => single += 1,
+            Tier::CQuoted => c_quoted += 1,
+        }
     }
+    let tier = match Tier::of(s) {
+        Tier::Bare => 0,
+        Tier::Single => 1,
+        Tier::CQuoted => 2,
+    };
+    let count = |n: usize| u16::try_from(n).unwrap_or(u16::MAX).to_be_bytes();
+    let ([single_hi, single_lo], [c_quoted_hi, c_quoted_lo]) = (count(single), count(c_quoted));
+    let [_, len_hi, len_mid, len_lo] =
+        u32::try_from(s.len()).unwrap_or(u32::MAX).min(0x00ff_ffff).to_be_bytes();
+    [tier, single_hi, single_lo, c_quoted_hi, c_quoted_lo, len_hi, len_mid, len_lo]
 }
 
 /// Shell-quotes the given [`Path`].
@@ -118,6 +349,12 @@ fn quotemeta_inner(s: &[u8]) -> String {
 /// This takes any `AsRef<Path>`, so accepts `&str`/`String`, `&Path`/`PathBuf`, `OsStr`/`OsString`,
 /// and so on.
 ///
+/// Glob characters are always quoted, and since `(` is too, so are bash's extglob patterns such as
+/// `+(foo)`. The output is therefore safe whether or not the script enables `shopt -s extglob`.
+///
+/// A leading `./` or `../` is left outside the quotes, as described for
+/// [`Quoter::preserve_dot_prefix`].
+///
 /// ```
 /// use quotemeta::quotemeta;
 ///
@@ -129,18 +366,251 @@ fn quotemeta_inner(s: &[u8]) -> String {
 /// assert_eq!(&quotemeta("\u{1f980}"), r"$'\360\237\246\200'");
 /// ```
 pub fn quotemeta(s: impl AsRef<Path>) -> String {
-    quotemeta_inner(s.as_ref().as_os_str().as_bytes())
+    Quoter::new().quote(s)
+}
+
+/// Shell-quotes the given bytes, avoiding a copy where possible.
+///
+/// If the input does not need quoting, it is returned as-is: borrowed input gives borrowed output
+/// and owned input is reused for the owned output. Otherwise, the result is the same as
+/// [`quotemeta`].
+///
+/// ```
+/// use quotemeta::quotemeta_cow_in;
+/// use std::borrow::Cow;
+///
+/// assert!(matches!(quotemeta_cow_in(Cow::Borrowed(b"/bin/cat")), Cow::Borrowed("/bin/cat")));
+/// assert_eq!(quotemeta_cow_in(Cow::Borrowed(b"a b")), "'a b'");
+/// ```
+#[must_use]
+pub fn quotemeta_cow_in(s: Cow<'_, [u8]>) -> Cow<'_, str> {
+    match (Tier::of(&s), s) {
+        // Safe bytes are all ASCII, so the lossy conversions never actually lose anything.
+        (Tier::Bare, Cow::Borrowed(s)) => String::from_utf8_lossy(s),
+        (Tier::Bare, Cow::Owned(s)) => Cow::Owned(
+            String::from_utf8(s)
+                .unwrap_or_else(|err| String::from_utf8_lossy(err.as_bytes()).into_owned()),
+        ),
+        (tier @ (Tier::Single | Tier::CQuoted), s) => {
+            Cow::Owned(Quoter::new().quote_bytes_as(&s, tier))
+        }
+    }
+}
+
+/// Adapts an [`io::Write`] to [`fmt::Write`], keeping hold of the first error.
+struct IoAdapter<'writer, W> {
+    inner: &'writer mut W,
+    error: Option<io::Error>,
+}
+
+impl<W: io::Write> fmt::Write for IoAdapter<'_, W> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        self.inner.write_all(s.as_bytes()).map_err(|err| {
+            self.error = Some(err);
+            fmt::Error
+        })
+    }
+}
+
+/// Shell-quotes the given bytes as [`quotemeta`] does, writing the result straight to `w`, and
+/// returns the [`Tier`] of quoting which was used.
+///
+/// The input is scanned once to find its tier, as [`quotemeta`] does anyway, and the output is
+/// never buffered. It is, however, written in small pieces, so an unbuffered `w` such as a
+/// [`File`](std::fs::File) is best wrapped in a [`BufWriter`](std::io::BufWriter).
+///
+/// # Errors
+///
+/// Returns any error from writing to `w`, in which case some of the output may have been written.
+///
+/// ```
+/// use quotemeta::{quotemeta_write_tier, Tier};
+///
+/// let mut out = Vec::new();
+/// assert_eq!(quotemeta_write_tier(b"a b", &mut out).unwrap(), Tier::Single);
+/// assert_eq!(out, b"'a b'");
+/// ```
+pub fn quotemeta_write_tier<W: io::Write>(s: &[u8], w: &mut W) -> io::Result<Tier> {
+    let tier = Tier::of(s);
+    let mut out = IoAdapter { inner: w, error: None };
+    match Quoter::new().write_bytes_as(s, tier, &mut out) {
+        Ok(()) => Ok(tier),
+        Err(fmt::Error) => Err(out.error.unwrap_or_else(|| io::Error::other("formatting failed"))),
+    }
+}
+
+/// Counts the bytes written to it, and fails as soon as there are more than the limit.
+struct LengthLimit {
+    len: usize,
+    limit: usize,
+}
+
+impl fmt::Write for LengthLimit {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        self.len += s.len();
+        if self.len > self.limit { Err(fmt::Error) } else { Ok(()) }
+    }
+}
+
+/// Returns whether the quoted form of the given [`Path`], as given by [`Quoter::quote`], would be
+/// longer than `limit` bytes.
+///
+/// The quoted form is never built up, and quoting stops as soon as the limit is passed, so this
+/// is a cheap way to decide whether an argument is too long for a command line, such as one built
+/// for `xargs -s`, and should instead be passed in a file or on standard input. Note that the
+/// kernel's own limit, `ARG_MAX`, also counts a NUL terminator and a pointer for each argument
+/// and the whole environment, so leave some room for those.
+///
+/// ```
+/// use quotemeta::{quoted_exceeds_limit, Quoter};
+///
+/// let quoter = Quoter::new();
+/// assert!(!quoted_exceeds_limit("a b", 5, &quoter));
+/// assert!(quoted_exceeds_limit("a b", 4, &quoter));
+/// ```
+pub fn quoted_exceeds_limit(s: impl AsRef<Path>, limit: usize, quoter: &Quoter) -> bool {
+    let mut counter = LengthLimit { len: 0, limit };
+    let s = quoter.prepared_infallible(s.as_ref().as_os_str().as_bytes());
+    quoter.write_bytes(&s, &mut counter).is_err()
+}
+
+/// Returns whether two different strings quote to the same thing with the given [`Quoter`].
+///
+/// With the default settings quoting is lossless, so this is always `false`; but a [`NulPolicy`]
+/// of [`Strip`](NulPolicy::Strip) or [`Replace`](NulPolicy::Replace), or normalization, can map
+/// different inputs to the same output. That is fine for display, but may matter if the quoted
+/// form is used as a key, say to deduplicate commands. Identical inputs never count as a
+/// collision, and nor does a string which the quoter refuses to quote.
+///
+/// ```
+/// use quotemeta::{quotes_collide, NulPolicy, Quoter};
+///
+/// assert!(!quotes_collide(b"a\0b", b"ab", &Quoter::new()));
+/// assert!(quotes_collide(b"a\0b", b"ab", &Quoter::new().nul_policy(NulPolicy::Strip)));
+/// ```
+#[must_use]
+pub fn quotes_collide(a: &[u8], b: &[u8], quoter: &Quoter) -> bool {
+    a != b
+        && matches!(
+            (quoter.try_quote(OsStr::from_bytes(a)), quoter.try_quote(OsStr::from_bytes(b))),
+            (Ok(quoted_a), Ok(quoted_b)) if quoted_a == quoted_b
+        )
+}
+
+/// The shortest segment [`quotemeta_chunked_cquote`] will write: `$'`, one escape, and `'`.
+const MIN_CHUNK: usize = 7;
+
+/// ANSI-C quotes the given [`Path`] as a run of adjacent `$'...'` segments, none of them longer
+/// than `chunk` bytes.
+///
+/// The shell joins the segments into a single word, so the result means exactly the same as one
+/// long `$'...'` string, but no single string in it is longer than the chunk size. This helps when
+/// a large binary blob has to get through something which limits the length of a token or a
+/// string, and means decoding it needs nothing but the shell itself. Each segment counts its `$'`
+/// and `'`, escapes are never split, and a `chunk` of less than 7 is taken as 7, which is enough
+/// for one escape.
+///
+/// Note that chunking does not get around `ARG_MAX`, nor the limit on a single argument
+/// (`MAX_ARG_STRLEN`, 128 KiB on Linux), which are applied to the decoded word when it is passed
+/// to an external command. Assign it to a variable or pass it to a builtin such as `printf` to
+/// avoid those.
+///
+/// ```
+/// use quotemeta::quotemeta_chunked_cquote;
+///
+/// assert_eq!(quotemeta_chunked_cquote("a\nb", 8), r"$'a\012'$'b'");
+/// assert_eq!(quotemeta_chunked_cquote("", 8), "$''");
+/// ```
+pub fn quotemeta_chunked_cquote(s: impl AsRef<Path>, chunk: usize) -> String {
+    let body = chunk.max(MIN_CHUNK) - 3;
+    let (mut out, mut len) = (String::from("$'"), 0);
+    for &c in s.as_ref().as_os_str().as_bytes() {
+        let escaped = c_escaped(c);
+        if len + escaped.len() > body {
+            out.push_str("'$'");
+            len = 0;
+        }
+        out.push_str(&escaped);
+        len += escaped.len();
+    }
+    out.push('\'');
+    out
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::quotemeta;
+    use crate::{
+        classification_digest, first_trouble, input_is_valid_utf8, is_safe, needs_quoting,
+        quoted_exceeds_limit, quotemeta, quotemeta_chunked_cquote, quotemeta_cow_in,
+        quotemeta_write_tier, quotes_collide, NulPolicy, QuoteStyle, Quoter, Tier,
+    };
+    #[cfg(feature = "unicode-normalization")] use crate::Normalization;
     #[cfg(unix)] use std::os::unix::ffi::OsStrExt;
     use std::{
+        borrow::Cow,
         ffi::{OsStr, OsString},
+        io::ErrorKind,
+        iter::Peekable,
         path::{Path, PathBuf},
+        str::Bytes,
     };
 
+    /// Parses the body of a `$'...'` string up to and including the closing quote.
+    fn unquote_c(bytes: &mut Peekable<Bytes<'_>>, out: &mut Vec<u8>) {
+        loop {
+            match bytes.next().expect("unterminated $'") {
+                b'\'' => return,
+                b'\\' => match bytes.next().expect("unterminated escape") {
+                    first @ b'0' ..= b'7' => {
+                        let mut value = first - b'0';
+                        for _ in 0 .. 2 {
+                            if let Some(&digit @ b'0' ..= b'7') = bytes.peek() {
+                                value = value * 8 + (digit - b'0');
+                                let _ = bytes.next();
+                            }
+                        }
+                        out.push(value);
+                    }
+                    b'a' => out.push(7),
+                    b'b' => out.push(8),
+                    b'e' => out.push(0x1b),
+                    b'f' => out.push(0xc),
+                    b'n' => out.push(b'\n'),
+                    b'r' => out.push(b'\r'),
+                    b't' => out.push(b'\t'),
+                    b'v' => out.push(0xb),
+                    literal @ (b'\\' | b'\'' | b'"' | b'?') => out.push(literal),
+                    other => panic!("unknown escape \\{}", char::from(other)),
+                },
+                other => out.push(other),
+            }
+        }
+    }
+
+    /// A reference tokenizer which splits a command line into words, handling just enough of the
+    /// shell's syntax to parse our output: bare words, `'...'` and `$'...'`.
+    pub(crate) fn shell_words(s: &str) -> Vec<Vec<u8>> {
+        let (mut words, mut bytes) = (Vec::new(), s.bytes().peekable());
+        let mut word: Option<Vec<u8>> = None;
+        while let Some(c) = bytes.next() {
+            match c {
+                b' ' | b'\t' | b'\n' => words.extend(word.take()),
+                b'\'' => {
+                    let out = word.get_or_insert_with(Vec::new);
+                    out.extend(bytes.by_ref().take_while(|&quoted| quoted != b'\''));
+                }
+                b'$' if bytes.peek() == Some(&b'\'') => {
+                    let _ = bytes.next();
+                    unquote_c(&mut bytes, word.get_or_insert_with(Vec::new));
+                }
+                _ if is_safe(c) || c == b'$' => word.get_or_insert_with(Vec::new).push(c),
+                _ => panic!("unexpected unquoted {:?}", char::from(c)),
+            }
+        }
+        words.extend(word);
+        words
+    }
+
     #[test]
     fn test_quotemeta() {
         assert_eq!(&quotemeta(""), "");
@@ -156,8 +626,191 @@ mod tests {
         assert_eq!(&quotemeta(OsStr::from_bytes(&[0xa3])), r"$'\243'");
     }
 
+    #[test]
+    fn test_round_trip() {
+        let inputs: &[&[u8]] = &[b"test", b"Hello, world!", b"isn't", b"\\\n3", b"\xa3\x00\xff"];
+        for &s in inputs {
+            assert_eq!(shell_words(&quotemeta(OsStr::from_bytes(s))), [s]);
+        }
+    }
+
+    // extglob patterns must be quoted even though `+` and `@` are safe on their own.
+    #[test]
+    fn test_extglob() {
+        for s in &["?(foo)", "*(foo)", "+(foo)", "@(foo)", "!(foo)", "foo+(bar)"] {
+            assert_eq!(quotemeta(s), format!("'{s}'"));
+        }
+        assert_eq!(&quotemeta("foo+bar@baz"), "foo+bar@baz");
+    }
+
+    #[test]
+    fn test_needs_quoting() {
+        assert!(!needs_quoting(""));
+        assert!(!needs_quoting("/bin/cat"));
+        assert!(needs_quoting("Hello, world!"));
+        assert!(needs_quoting("\n"));
+        assert!(needs_quoting(OsStr::from_bytes(&[0xa3])));
+    }
+
+    #[test]
+    fn test_first_trouble() {
+        assert_eq!(first_trouble(b""), None);
+        assert_eq!(first_trouble(b"/usr/bin/env"), None);
+        assert_eq!(first_trouble(b" leading"), Some((0, Tier::Single)));
+        assert_eq!(first_trouble(b"\nleading"), Some((0, Tier::CQuoted)));
+        assert_eq!(first_trouble(b"mid dle\n"), Some((3, Tier::Single)));
+        assert_eq!(first_trouble(b"isn't"), Some((3, Tier::CQuoted)));
+        assert_eq!(first_trouble(b"end*"), Some((3, Tier::Single)));
+        assert_eq!(first_trouble("caf\u{e9}".as_bytes()), Some((3, Tier::CQuoted)));
+    }
+
+    #[test]
+    fn test_input_is_valid_utf8() {
+        assert!(input_is_valid_utf8(""));
+        assert!(input_is_valid_utf8("/bin/cat"));
+        assert!(input_is_valid_utf8("caf\u{e9} \u{1f980}"));
+        assert!(!input_is_valid_utf8(OsStr::from_bytes(b"caf\xe9")));
+        assert!(!input_is_valid_utf8(OsStr::from_bytes(b"\xc3")));
+    }
+
+    #[test]
+    fn test_classification_digest() {
+        assert_eq!(classification_digest(b""), [0; 8]);
+        assert_eq!(classification_digest(b"/bin/cat"), [0, 0, 0, 0, 0, 0, 0, 8]);
+        assert_eq!(classification_digest(b"it's a*"), [2, 0, 2, 0, 1, 0, 0, 7]);
+        assert_eq!(classification_digest(b"my file"), classification_digest(b"my file"));
+        // Inputs of the same length but different tiers differ.
+        let (bare, single, c_quoted) = (b"abc", b"a c", b"a\\c");
+        assert_ne!(classification_digest(bare), classification_digest(single));
+        assert_ne!(classification_digest(single), classification_digest(c_quoted));
+        assert_eq!(classification_digest(c_quoted).first(), Some(&2));
+        // The counts saturate.
+        let long = vec![b' '; 70_000];
+        assert_eq!(classification_digest(&long), [1, 0xff, 0xff, 0, 0, 0x01, 0x11, 0x70]);
+        let huge = vec![b'x'; 0x0100_0000];
+        assert_eq!(classification_digest(&huge), [0, 0, 0, 0, 0, 0xff, 0xff, 0xff]);
+    }
+
+    #[test]
+    fn test_quotemeta_cow_in() {
+        // Borrowed and safe: borrowed output over the input.
+        let borrowed = b"/bin/cat";
+        assert!(matches!(
+            quotemeta_cow_in(Cow::Borrowed(borrowed)),
+            Cow::Borrowed(output) if output.as_ptr() == borrowed.as_ptr()
+        ));
+        // Borrowed and unsafe
+        assert_eq!(quotemeta_cow_in(Cow::Borrowed(b"a b")), Cow::<str>::Owned("'a b'".into()));
+        assert_eq!(quotemeta_cow_in(Cow::Borrowed(b"\xa3")), r"$'\243'");
+        // Owned, safe and unsafe. The safe case reuses the allocation.
+        let owned = b"test".to_vec();
+        let ptr = owned.as_ptr();
+        assert!(matches!(
+            quotemeta_cow_in(Cow::Owned(owned)),
+            Cow::Owned(output) if output.as_ptr() == ptr
+        ));
+        assert_eq!(quotemeta_cow_in(Cow::Owned(b"isn't".to_vec())), r"$'isn\'t'");
+    }
+
+    #[test]
+    fn test_quotemeta_write_tier() {
+        let cases: [(&[u8], &[u8], Tier); 5] = [
+            (b"", b"", Tier::Bare),
+            (b"/bin/cat", b"/bin/cat", Tier::Bare),
+            (b"my file", b"'my file'", Tier::Single),
+            (b"tab\there", br"$'tab\011here'", Tier::CQuoted),
+            (b"./it's", br"./$'it\'s'", Tier::CQuoted),
+        ];
+        for (s, quoted, tier) in cases {
+            let mut out = Vec::new();
+            assert_eq!(quotemeta_write_tier(s, &mut out).expect("writes"), tier);
+            assert_eq!(out, quoted);
+            assert_eq!(out, quotemeta(OsStr::from_bytes(s)).into_bytes());
+        }
+        // Errors from the writer are passed on.
+        let mut buf = [0; 3];
+        let mut short = buf.as_mut_slice();
+        let err = quotemeta_write_tier(b"a b", &mut short).expect_err("short write");
+        assert_eq!(err.kind(), ErrorKind::WriteZero);
+        assert_eq!(&buf, b"'a ");
+    }
+
+    #[test]
+    fn test_quoted_exceeds_limit() {
+        let quoter = Quoter::new();
+        assert!(!quoted_exceeds_limit("", 0, &quoter));
+        assert!(!quoted_exceeds_limit("/bin/cat", 8, &quoter));
+        assert!(quoted_exceeds_limit("/bin/cat", 7, &quoter));
+        // The limit applies to the quoted form, not the original.
+        assert!(quoted_exceeds_limit("\n", 1, &quoter));
+        assert!(!quoted_exceeds_limit("\n", 7, &quoter));
+        let long = "x ".repeat(100_000);
+        assert!(!quoted_exceeds_limit(&long, 200_002, &quoter));
+        assert!(quoted_exceeds_limit(&long, 200_001, &quoter));
+        assert!(quoted_exceeds_limit(&long, 4096, &quoter));
+        // The quoter's settings are taken into account.
+        let isolate = Quoter::new().style(QuoteStyle::IsolateDangerous);
+        assert_eq!(isolate.quote("it's"), r"it$'\''s");
+        assert!(!quoted_exceeds_limit("it's", 8, &isolate));
+        assert!(quoted_exceeds_limit("it's", 7, &isolate));
+    }
+
+    #[test]
+    fn test_quotes_collide() {
+        let mut corpus: Vec<Vec<u8>> = (0 ..= 255).map(|c| vec![c]).collect();
+        corpus.extend(["", "a b", "it's", "\\", "\\\\", "\0", "\0\0", "a\0b", "ab", "\u{e9}"].map(
+            |s| s.as_bytes().to_vec(),
+        ));
+        corpus.extend([b"\xff\xfe".to_vec(), b"e\xcc\x81".to_vec(), b"\xc3\xa9".to_vec()]);
+        let quoter = Quoter::new();
+        for a in &corpus {
+            for b in &corpus {
+                assert!(!quotes_collide(a, b, &quoter), "{:?} {:?}", a, b);
+            }
+        }
+        // Lossy settings can collide, but only where they actually lose something.
+        let strip = Quoter::new().nul_policy(NulPolicy::Strip);
+        assert!(quotes_collide(b"\0", b"", &strip));
+        assert!(quotes_collide(b"a\0b", b"ab", &strip));
+        assert!(!quotes_collide(b"a\0b", b"a b", &strip));
+        let replace = Quoter::new().nul_policy(NulPolicy::Replace(b'_'));
+        assert!(quotes_collide(b"a\0b", b"a_b", &replace));
+        // A string which cannot be quoted collides with nothing.
+        let error = Quoter::new().nul_policy(NulPolicy::Error);
+        assert!(!quotes_collide(b"\0", b"\0\0", &error));
+    }
+
+    #[cfg(feature = "unicode-normalization")]
+    #[test]
+    fn test_quotes_collide_normalized() {
+        let nfc = Quoter::new().normalize(Normalization::Nfc);
+        assert!(quotes_collide("e\u{301}".as_bytes(), "\u{e9}".as_bytes(), &nfc));
+        assert!(!quotes_collide("e\u{301}".as_bytes(), "\u{e9}".as_bytes(), &Quoter::new()));
+    }
+
+    #[test]
+    fn test_quotemeta_chunked_cquote() {
+        let blob: Vec<u8> = (0 ..= 255).cycle().take(10_000).collect();
+        let quoted = quotemeta_chunked_cquote(OsStr::from_bytes(&blob), 100);
+        let body = quoted.strip_prefix("$'").and_then(|rest| rest.strip_suffix('\'')).expect("$'");
+        let segments: Vec<&str> = body.split("'$'").collect();
+        assert!(segments.len() > 100, "{:?}", segments.len());
+        assert!(segments.iter().all(|segment| segment.len() + 3 <= 100), "{:?}", quoted);
+        // All but the last segment are as full as the escapes allow.
+        let full = segments.get(.. segments.len() - 1).expect("in bounds");
+        assert!(full.iter().all(|segment| segment.len() + 3 > 96), "{:?}", quoted);
+        assert_eq!(shell_words(&quoted), [blob]);
+        // Escapes are never split, even when the chunk is too small to be useful.
+        assert_eq!(quotemeta_chunked_cquote("\n\n", 0), r"$'\012'$'\012'");
+        assert_eq!(quotemeta_chunked_cquote("it's", 7), r"$'it\''$'s'");
+        assert_eq!(quotemeta_chunked_cquote("plain", 100), "$'plain'");
+        assert_eq!(shell_words(&quotemeta_chunked_cquote("", 100)), [b""]);
+    }
+
     // merely a compilation test to ensure that we accept the given types.
     #[test]
+    #[expect(clippy::unnecessary_to_owned)] //// the point is to pass a `String`
+    #[cfg_attr(feature = "clippy-insane", expect(clippy::manual_string_new, clippy::str_to_string))]
     fn test_types() {
         let _ = quotemeta("");
         let _ = quotemeta("".to_string());
@@ -0,0 +1,187 @@
+//! Quoting for the recipes of Makefile rules.
+
+use crate::{command::join_bash, quotemeta};
+use std::{error::Error, fmt, path::Path};
+
+/// Shell-quotes the given [`Path`] for use in a Makefile recipe.
+///
+/// Make expands variable references in a recipe before handing each line to the shell, so this
+/// applies [`quotemeta`] and then doubles every `$`, which Make turns back into a single one. The
+/// quoted form never contains a newline, so it cannot break the recipe line or start a new one.
+///
+/// The result may use `$'...'`, which the `/bin/sh` that Make uses by default need not support.
+/// Set `SHELL := /bin/bash` in the Makefile if that matters.
+///
+/// ```
+/// use quotemeta::quotemeta_make;
+///
+/// assert_eq!(&quotemeta_make("$HOME"), "'$$HOME'");
+/// assert_eq!(&quotemeta_make("\n"), r"$$'\012'");
+/// ```
+pub fn quotemeta_make(s: impl AsRef<Path>) -> String { quotemeta(s).replace('$', "$$") }
+
+/// The error returned when a string cannot be used as the name of a Make target or prerequisite.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(
+    feature = "clippy-insane",
+    expect(clippy::exhaustive_structs, reason = "the name is all there is to report")
+)]
+pub struct InvalidMakeName(pub String);
+
+impl fmt::Display for InvalidMakeName {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:?} cannot be used as a Make target or prerequisite", self.0)
+    }
+}
+
+impl Error for InvalidMakeName {}
+
+/// Returns whether the name can be written as-is in the first line of a rule.
+///
+/// Make has no quoting for names, so this rules out anything which it would take as a separator
+/// or as syntax of its own: whitespace and control characters, `:`, `;`, `|` and `=`, `#`, `$`,
+/// `%` (a pattern rule), `\`, wildcards, the parentheses of an archive member, and a leading `~`.
+fn is_make_name(name: &str) -> bool {
+    !name.is_empty()
+        && !name.starts_with('~')
+        && !name.chars().any(|c| {
+            c.is_whitespace()
+                || c.is_control()
+                || matches!(c, ':' | ';' | '|' | '=' | '#' | '$' | '%' | '\\')
+                || matches!(c, '*' | '?' | '[' | ']' | '(' | ')')
+        })
+}
+
+/// Returns the name if it can be written as-is in the first line of a rule.
+fn make_name(name: &str) -> Result<&str, InvalidMakeName> {
+    if is_make_name(name) { Ok(name) } else { Err(InvalidMakeName(name.to_owned())) }
+}
+
+/// Generates a Makefile rule which builds `target` from `prereqs` by running the given command.
+///
+/// The result is a `target: prereqs` line followed by the recipe: a single line made up of a tab
+/// and the arguments, each quoted by [`quotemeta_make`] and separated by spaces, with empty
+/// arguments written as `''`. If there are no arguments, there is no recipe line. The rule ends
+/// with a newline.
+///
+/// Make strips any `@`, `-` and `+` from the start of a recipe line, taking them as prefixes which
+/// silence the command, ignore its failure or run it anyway, so a first argument which starts with
+/// one of these is single-quoted even if [`quotemeta_make`] would leave it bare.
+///
+/// # Errors
+///
+/// Returns [`InvalidMakeName`] if the target or one of the prerequisites is empty, or contains a
+/// character which Make would not take literally there, such as a space, `:`, `$` or `%`. Make
+/// has no way to quote these.
+///
+/// ```
+/// use quotemeta::makefile_rule;
+///
+/// let rule = makefile_rule("out.txt", &["in.txt"], ["sort", "-o", "out.txt", "in.txt"]);
+/// assert_eq!(rule.unwrap(), "out.txt: in.txt\n\tsort -o out.txt in.txt\n");
+/// assert!(makefile_rule("my file", &[], ["true"]).is_err());
+/// ```
+pub fn makefile_rule<I, S>(
+    target: &str,
+    prereqs: &[&str],
+    command_args: I,
+) -> Result<String, InvalidMakeName>
+where
+    I: IntoIterator<Item = S>,
+    S: AsRef<Path>,
+{
+    let mut rule = format!("{}:", make_name(target)?);
+    for prereq in prereqs {
+        rule.push(' ');
+        rule.push_str(make_name(prereq)?);
+    }
+    let mut recipe = join_bash(command_args);
+    if recipe.starts_with(['@', '-', '+']) {
+        // The first word is bare, so it contains neither a space nor a single quote.
+        let end = recipe.find(' ').unwrap_or(recipe.len());
+        recipe.insert(end, '\'');
+        recipe.insert(0, '\'');
+    }
+    if !recipe.is_empty() {
+        rule.push_str("\n\t");
+        rule.push_str(&recipe.replace('$', "$$"));
+    }
+    rule.push('\n');
+    Ok(rule)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{makefile_rule, quotemeta_make, tests::shell_words, InvalidMakeName};
+    #[cfg(unix)] use std::os::unix::ffi::OsStrExt;
+    use std::ffi::OsStr;
+
+    /// Undoes Make's expansion of `$$` in a recipe line, which is all there is to expand.
+    fn make_expand(line: &str) -> String {
+        assert!(!line.replace("$$", "").contains('$'), "{:?}", line);
+        line.replace("$$", "$")
+    }
+
+    #[test]
+    fn test_quotemeta_make() {
+        assert_eq!(&quotemeta_make("plain"), "plain");
+        assert_eq!(&quotemeta_make("a b"), "'a b'");
+        assert_eq!(&quotemeta_make("$HOME/$$"), "'$$HOME/$$$$'");
+        assert_eq!(&quotemeta_make("it's"), r"$$'it\'s'");
+        let bytes: &[u8] = b"$\n\xff";
+        let quoted = quotemeta_make(OsStr::from_bytes(bytes));
+        assert!(!quoted.contains('\n'));
+        assert_eq!(shell_words(&make_expand(&quoted)), [bytes]);
+    }
+
+    #[test]
+    fn test_makefile_rule() {
+        let rule = makefile_rule("report.pdf", &["my.tex", "refs.bib"], [
+            "latexmk",
+            "-jobname=$out",
+            "my report.tex",
+        ])
+        .expect("valid rule");
+        let recipe_line = "\tlatexmk '-jobname=$$out' 'my report.tex'\n";
+        assert_eq!(rule, format!("report.pdf: my.tex refs.bib\n{recipe_line}"));
+        let recipe = rule.lines().nth(1).expect("recipe").strip_prefix('\t').expect("tab");
+        let words = shell_words(&make_expand(recipe));
+        assert_eq!(words, [&b"latexmk"[..], b"-jobname=$out", b"my report.tex"]);
+
+        assert_eq!(makefile_rule("all", &[], Vec::<&str>::new()).expect("valid rule"), "all:\n");
+        assert_eq!(
+            makefile_rule("clean", &[], ["rm", ""]).expect("valid rule"),
+            "clean:\n\trm ''\n"
+        );
+        let unicode = makefile_rule("caf\u{e9}", &["dir/a.o"], ["true"]).expect("valid rule");
+        assert_eq!(unicode, "caf\u{e9}: dir/a.o\n\ttrue\n");
+        for name in ["", "a b", "a:b", "a;b", "a=b", "#a", "$(X)", "%.o", "a\\b", "*.c", "~/a"] {
+            let err = makefile_rule(name, &[], ["true"]).expect_err("invalid name");
+            assert_eq!(err, InvalidMakeName(name.to_owned()));
+            assert_eq!(
+                makefile_rule("ok", &["fine", name], ["true"]).expect_err("invalid name"),
+                err
+            );
+        }
+        assert_eq!(
+            InvalidMakeName(String::from("a b")).to_string(),
+            r#""a b" cannot be used as a Make target or prerequisite"#
+        );
+    }
+
+    #[test]
+    fn test_makefile_rule_prefixes() {
+        // Make would strip these from the recipe line instead of running them.
+        for (args, recipe) in [
+            (&["@echo", "hi"][..], "'@echo' hi"),
+            (&["-rm"], "'-rm'"),
+            (&["+make", "-C", "sub"], "'+make' -C sub"),
+            (&["@$x", "-f"], "'@$$x' -f"),
+        ] {
+            let rule = makefile_rule("t", &[], args).expect("valid rule");
+            assert_eq!(rule, format!("t:\n\t{recipe}\n"));
+            let words = shell_words(&make_expand(recipe));
+            assert_eq!(words, args.iter().map(|arg| arg.as_bytes()).collect::<Vec<_>>());
+        }
+    }
+}
@@ -0,0 +1,484 @@
+//! Quoting which reports what it had to do, for callers which need to be strict about it.
+
+use crate::{c_escaped, quotemeta, Quoter, Tier};
+#[cfg(unix)] use std::os::unix::ffi::OsStrExt;
+#[cfg(feature = "regex")] use std::str;
+use std::{error::Error, ffi::OsStr, fmt, ops::Range, path::Path};
+
+/// Why a string cannot be represented as a shell word.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum UnrepresentableReason {
+    /// The string contains a NUL byte at the given offset. Arguments and variables are C strings,
+    /// so the shell would silently truncate the string there.
+    Nul(usize),
+}
+
+impl fmt::Display for UnrepresentableReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            Self::Nul(offset) => {
+                write!(f, "NUL byte at offset {offset} cannot be passed to a shell")
+            }
+        }
+    }
+}
+
+impl Error for UnrepresentableReason {}
+
+/// The result of [`quotemeta_outcome`].
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum QuoteOutcome {
+    /// The string did not need quoting, and is returned as-is.
+    Bare(String),
+    /// The string needed quoting, and this is the quoted form.
+    Quoted(String),
+    /// The string cannot be represented at all.
+    Unrepresentable(UnrepresentableReason),
+}
+
+/// Shell-quotes the given [`Path`] with the given [`Quoter`], and reports which of the possible
+/// outcomes occurred.
+///
+/// This saves a strict generator from separately calling [`needs_quoting`](crate::needs_quoting)
+/// and checking for input which the shell cannot represent. [`Quoter::quote`] happily quotes a NUL
+/// byte as `$'\000'`, for example, but bash then truncates the string there.
+///
+/// A NUL byte is therefore reported as [`QuoteOutcome::Unrepresentable`] by default, as for
+/// [`Quoter::try_quote`], and even under [`NulPolicy::Escape`](crate::NulPolicy::Escape), which
+/// is meant for display. Under
+/// [`NulPolicy::Strip`](crate::NulPolicy::Strip) or
+/// [`NulPolicy::Replace`](crate::NulPolicy::Replace), the altered string is quoted as usual.
+///
+/// ```
+/// use quotemeta::{quotemeta_outcome, QuoteOutcome, Quoter, UnrepresentableReason};
+///
+/// let quoter = Quoter::new();
+/// assert_eq!(quotemeta_outcome("/bin/sh", &quoter), QuoteOutcome::Bare("/bin/sh".into()));
+/// assert_eq!(quotemeta_outcome("a b", &quoter), QuoteOutcome::Quoted("'a b'".into()));
+/// assert_eq!(
+///     quotemeta_outcome("a\0b", &quoter),
+///     QuoteOutcome::Unrepresentable(UnrepresentableReason::Nul(1))
+/// );
+/// ```
+pub fn quotemeta_outcome(s: impl AsRef<Path>, quoter: &Quoter) -> QuoteOutcome {
+    let bytes = s.as_ref().as_os_str().as_bytes();
+    let output = match quoter.try_quote_strict(bytes) {
+        Ok(output) => output,
+        Err(reason) => return QuoteOutcome::Unrepresentable(reason),
+    };
+    if output.as_bytes() == bytes {
+        QuoteOutcome::Bare(output)
+    } else {
+        QuoteOutcome::Quoted(output)
+    }
+}
+
+/// A quoted argument, along with what was needed to quote it. This is returned by [`quote_arg`].
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct QuotedArg {
+    quoted: String,
+    tier: Tier,
+    original_len: usize,
+    round_trips: bool,
+}
+
+impl QuotedArg {
+    /// Returns the quoted form of the argument, as given by [`quotemeta`].
+    #[must_use]
+    pub fn as_str(&self) -> &str { &self.quoted }
+
+    /// Returns the tier of quoting which the argument required.
+    #[must_use]
+    pub const fn tier(&self) -> Tier { self.tier }
+
+    /// Returns the length of the original argument, in bytes.
+    #[must_use]
+    pub const fn original_len(&self) -> usize { self.original_len }
+
+    /// Returns whether the shell will pass on the original argument intact. This is only `false`
+    /// if it contains a NUL byte, for the reasons given under [`UnrepresentableReason::Nul`].
+    #[must_use]
+    pub const fn round_trips(&self) -> bool { self.round_trips }
+
+    /// Unwraps the quoted form of the argument.
+    #[must_use]
+    pub fn into_string(self) -> String { self.quoted }
+}
+
+impl fmt::Display for QuotedArg {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result { f.write_str(&self.quoted) }
+}
+
+/// Shell-quotes the given [`Path`] as [`quotemeta`] does, and returns it along with some metadata
+/// about it, for tools which would otherwise have to work that out again.
+///
+/// ```
+/// use quotemeta::{quote_arg, Tier};
+///
+/// let arg = quote_arg("my file");
+/// assert_eq!(arg.to_string(), "'my file'");
+/// assert_eq!(arg.tier(), Tier::Single);
+/// assert_eq!(arg.original_len(), 7);
+/// assert!(arg.round_trips());
+/// ```
+pub fn quote_arg(s: impl AsRef<Path>) -> QuotedArg {
+    let bytes = s.as_ref().as_os_str().as_bytes();
+    QuotedArg {
+        quoted: quotemeta(&s),
+        tier: Tier::of(bytes),
+        original_len: bytes.len(),
+        round_trips: !bytes.contains(&0),
+    }
+}
+
+/// The error returned by [`quotemeta_require_match`] when a value is not allowed.
+#[cfg(feature = "regex")]
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct PolicyViolation {
+    quoted: String,
+    pattern: String,
+}
+
+#[cfg(feature = "regex")]
+impl PolicyViolation {
+    /// Returns the rejected value, quoted as by [`quotemeta`] so that it is safe to show.
+    #[must_use]
+    pub fn quoted(&self) -> &str { &self.quoted }
+
+    /// Returns the pattern which the value failed to match.
+    #[must_use]
+    pub fn pattern(&self) -> &str { &self.pattern }
+}
+
+#[cfg(feature = "regex")]
+impl fmt::Display for PolicyViolation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} does not match the allowed pattern /{}/", self.quoted, self.pattern)
+    }
+}
+
+#[cfg(feature = "regex")]
+impl Error for PolicyViolation {}
+
+/// Shell-quotes the given bytes as [`quotemeta`] does, but only if they match the given pattern.
+///
+/// This lets a generator enforce a policy such as "filenames must match `[\w.-]+`" and fail loudly
+/// on a name which breaks it, rather than quietly quoting something unexpected. As with
+/// [`Regex::is_match`](regex::Regex::is_match), the pattern may match anywhere in the value, so
+/// anchor it with `^...$` to constrain the whole value. Bytes which are not valid UTF-8 never
+/// match, whatever the pattern.
+///
+/// This requires the `regex` feature.
+///
+/// # Errors
+///
+/// Returns a [`PolicyViolation`] if the value does not match the pattern.
+///
+/// ```
+/// use quotemeta::quotemeta_require_match;
+/// use regex::Regex;
+///
+/// let pattern = Regex::new(r"^[\w.-]+$").unwrap();
+/// assert_eq!(quotemeta_require_match(b"report-2.txt", &pattern).unwrap(), "report-2.txt");
+/// assert!(quotemeta_require_match(b"; rm -rf ~", &pattern).is_err());
+/// ```
+#[cfg(feature = "regex")]
+pub fn quotemeta_require_match(
+    s: &[u8], pattern: &regex::Regex,
+) -> Result<String, PolicyViolation> {
+    let quoted = quotemeta(OsStr::from_bytes(s));
+    if str::from_utf8(s).is_ok_and(|text| pattern.is_match(text)) {
+        Ok(quoted)
+    } else {
+        Err(PolicyViolation { quoted, pattern: pattern.as_str().to_owned() })
+    }
+}
+
+/// One step taken by [`quotemeta_traced`]: a range of the input, and what it was replaced with.
+///
+/// An empty range marks text which was inserted there, such as an opening quote.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Transform {
+    input: Range<usize>,
+    original: Vec<u8>,
+    replacement: String,
+}
+
+impl Transform {
+    /// Returns the range of input bytes which were replaced.
+    #[must_use]
+    pub fn input(&self) -> Range<usize> { self.input.clone() }
+
+    /// Returns the input bytes which were replaced, which are empty for an insertion.
+    #[must_use]
+    pub fn original(&self) -> &[u8] { &self.original }
+
+    /// Returns the text which replaced them.
+    #[must_use]
+    pub fn replacement(&self) -> &str { &self.replacement }
+}
+
+impl fmt::Display for Transform {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let Range { start, end } = self.input;
+        if self.original.is_empty() {
+            return write!(f, "insert `{}` at byte {start}", self.replacement);
+        }
+        write!(f, "bytes {start}..{end} (")?;
+        for (index, c) in self.original.iter().enumerate() {
+            let separator = if index == 0 { "" } else { " " };
+            write!(f, "{separator}{c:#04x}")?;
+        }
+        write!(f, ") -> `{}`", self.replacement)
+    }
+}
+
+/// Shell-quotes the given bytes as [`quotemeta`] does, and returns a trace of every change it
+/// made, for debugging and for showing how the quoting works.
+///
+/// Each [`Transform`] replaces a range of the input, or inserts text where the range is empty.
+/// The transforms are in order and do not overlap, and the input bytes between them are copied
+/// through unchanged, so applying them in turn to the input gives the quoted output.
+///
+/// ```
+/// use quotemeta::quotemeta_traced;
+///
+/// let (quoted, trace) = quotemeta_traced(b"a\nb");
+/// assert_eq!(&quoted, r"$'a\012b'");
+/// let steps: Vec<String> = trace.iter().map(ToString::to_string).collect();
+/// assert_eq!(steps[0], "insert `$'` at byte 0");
+/// assert_eq!(steps[1], r"bytes 1..2 (0x0a) -> `\012`");
+/// assert_eq!(steps[2], "insert `'` at byte 3");
+/// ```
+#[must_use]
+pub fn quotemeta_traced(s: &[u8]) -> (String, Vec<Transform>) {
+    let tier = Tier::of(s);
+    if tier == Tier::Bare {
+        return (quotemeta(OsStr::from_bytes(s)), Vec::new());
+    }
+    let insert = |offset: usize, text: &str| Transform {
+        input: offset .. offset,
+        original: Vec::new(),
+        replacement: text.to_owned(),
+    };
+    let (prefix, body) = match Quoter::new().split_dot_prefix(s) {
+        (prefix, rest) if !rest.is_empty() => (prefix, rest),
+        _ => (&[][..], s),
+    };
+    let opening = if tier == Tier::CQuoted { "$'" } else { "'" };
+    let mut trace = vec![insert(prefix.len(), opening)];
+    if tier == Tier::CQuoted {
+        for (index, &c) in body.iter().enumerate() {
+            if Tier::of_byte(c) == Tier::CQuoted {
+                let offset = prefix.len() + index;
+                let input = offset .. offset + 1;
+                trace.push(Transform { input, original: vec![c], replacement: c_escaped(c) });
+            }
+        }
+    }
+    trace.push(insert(s.len(), "'"));
+
+    let (mut quoted, mut copied) = (String::with_capacity(s.len() + 3), 0);
+    for transform in &trace {
+        let unchanged = s.get(copied .. transform.input.start).unwrap_or_default();
+        quoted.push_str(&String::from_utf8_lossy(unchanged));
+        quoted.push_str(&transform.replacement);
+        copied = transform.input.end;
+    }
+    (quoted, trace)
+}
+
+/// Maps a byte offset in the output of [`quotemeta`] back to the input byte it came from, for
+/// tools which highlight the source of whatever is under the cursor.
+///
+/// Every byte of an escape maps to the single input byte which it stands for, so each of the
+/// four bytes of `\012` gives the offset of the newline. Text which quoting only adds, such as an
+/// opening `$'` or a closing quote, has no input byte and gives `None`, as does an offset past
+/// the end of the output. This is worked out from the trace given by [`quotemeta_traced`].
+///
+/// ```
+/// use quotemeta::output_to_input_offset;
+///
+/// // The output is `$'a\012b'`.
+/// assert_eq!(output_to_input_offset(b"a\nb", 0), None);
+/// assert_eq!(output_to_input_offset(b"a\nb", 2), Some(0));
+/// assert_eq!(output_to_input_offset(b"a\nb", 5), Some(1));
+/// assert_eq!(output_to_input_offset(b"a\nb", 7), Some(2));
+/// ```
+#[must_use]
+pub fn output_to_input_offset(s: &[u8], out_offset: usize) -> Option<usize> {
+    let (_, trace) = quotemeta_traced(s);
+    let (mut remaining, mut copied) = (out_offset, 0);
+    for transform in &trace {
+        let unchanged = transform.input.start - copied;
+        if remaining < unchanged {
+            return Some(copied + remaining);
+        }
+        remaining -= unchanged;
+        if remaining < transform.replacement.len() {
+            return (!transform.original.is_empty()).then_some(transform.input.start);
+        }
+        remaining -= transform.replacement.len();
+        copied = transform.input.end;
+    }
+    let offset = copied + remaining;
+    (offset < s.len()).then_some(offset)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        output_to_input_offset, quote_arg, quotemeta, quotemeta_outcome, quotemeta_traced,
+        NulPolicy, QuoteOutcome, QuoteStyle, Quoter, Tier, Transform, UnrepresentableReason,
+    };
+    #[cfg(unix)] use std::os::unix::ffi::OsStrExt;
+    use std::ffi::OsStr;
+
+    #[test]
+    fn test_quotemeta_outcome() {
+        let quoter =
+            Quoter::new().style(QuoteStyle::IsolateDangerous).c_quote_delimiters("E'", "'");
+        let outcome = |s: &[u8]| quotemeta_outcome(OsStr::from_bytes(s), &quoter);
+        assert_eq!(outcome(b""), QuoteOutcome::Bare(String::new()));
+        assert_eq!(outcome(b"safe_name"), QuoteOutcome::Bare(String::from("safe_name")));
+        assert_eq!(outcome(b"a b"), QuoteOutcome::Quoted(String::from("'a b'")));
+        assert_eq!(outcome(b"it's"), QuoteOutcome::Quoted(String::from(r"itE'\''s")));
+        assert_eq!(outcome(b"\xff"), QuoteOutcome::Quoted(String::from(r"E'\377'")));
+        assert_eq!(outcome(b"\0"), QuoteOutcome::Unrepresentable(UnrepresentableReason::Nul(0)));
+        assert_eq!(
+            outcome(b"ab\0\0"),
+            QuoteOutcome::Unrepresentable(UnrepresentableReason::Nul(2))
+        );
+        let stripped = Quoter::new().nul_policy(NulPolicy::Strip);
+        assert_eq!(quotemeta_outcome("a\0b", &stripped), QuoteOutcome::Quoted(String::from("ab")));
+        let strict = Quoter::new().nul_policy(NulPolicy::Error);
+        assert_eq!(
+            quotemeta_outcome("a\0b", &strict),
+            QuoteOutcome::Unrepresentable(UnrepresentableReason::Nul(1))
+        );
+        assert_eq!(
+            UnrepresentableReason::Nul(2).to_string(),
+            "NUL byte at offset 2 cannot be passed to a shell"
+        );
+    }
+
+    #[test]
+    fn test_quote_arg() {
+        let bare = quote_arg("/bin/cat");
+        assert_eq!(bare.as_str(), "/bin/cat");
+        assert_eq!(bare.tier(), Tier::Bare);
+        assert_eq!(bare.original_len(), 8);
+        assert!(bare.round_trips());
+
+        let single = quote_arg("a b");
+        assert_eq!(single.to_string(), "'a b'");
+        assert_eq!(single.tier(), Tier::Single);
+        assert_eq!(single.original_len(), 3);
+        assert!(single.round_trips());
+
+        let c_quoted = quote_arg(OsStr::from_bytes(b"\xff\0"));
+        assert_eq!(c_quoted.as_str(), r"$'\377\000'");
+        assert_eq!(c_quoted.tier(), Tier::CQuoted);
+        assert_eq!(c_quoted.original_len(), 2);
+        assert!(!c_quoted.round_trips());
+        assert_eq!(c_quoted.into_string(), r"$'\377\000'");
+
+        assert_eq!(quote_arg("").tier(), Tier::Bare);
+    }
+
+    /// Applies the transforms to the input, as described for [`quotemeta_traced`].
+    fn apply(s: &[u8], trace: &[Transform]) -> Vec<u8> {
+        let (mut out, mut copied) = (Vec::new(), 0);
+        for transform in trace {
+            let input = transform.input();
+            assert!(input.start >= copied, "transforms overlap");
+            out.extend_from_slice(s.get(copied .. input.start).expect("in bounds"));
+            assert_eq!(s.get(input.clone()).expect("in bounds"), transform.original());
+            out.extend_from_slice(transform.replacement().as_bytes());
+            copied = input.end;
+        }
+        out.extend_from_slice(s.get(copied ..).expect("in bounds"));
+        out
+    }
+
+    #[test]
+    fn test_quotemeta_traced() {
+        let inputs: &[&[u8]] =
+            &[b"", b"/bin/cat", b"a b", b"it's", b"\0\xff\n", b"./a b", b"../../x\ty", b"./"];
+        for &s in inputs {
+            let (quoted, trace) = quotemeta_traced(s);
+            assert_eq!(quoted, quotemeta(OsStr::from_bytes(s)));
+            assert_eq!(apply(s, &trace), quoted.as_bytes());
+        }
+        assert_eq!(quotemeta_traced(b"plain").1, []);
+
+        let (quoted, trace) = quotemeta_traced(b"./it's");
+        assert_eq!(quoted, r"./$'it\'s'");
+        let steps: Vec<String> = trace.iter().map(ToString::to_string).collect();
+        assert_eq!(
+            steps,
+            ["insert `$'` at byte 2", r"bytes 4..5 (0x27) -> `\'`", "insert `'` at byte 6"]
+        );
+        let (_, single) = quotemeta_traced(b"a b");
+        assert_eq!(single.iter().map(Transform::replacement).collect::<Vec<_>>(), ["'", "'"]);
+        assert_eq!(single.last().map(Transform::input), Some(3 .. 3));
+    }
+
+    #[test]
+    fn test_output_to_input_offset() {
+        let (s, quoted): (&[u8], &str) = (b"a\n\xffb", r"$'a\012\377b'");
+        assert_eq!(quotemeta(OsStr::from_bytes(s)), quoted);
+        let offsets: Vec<Option<usize>> =
+            (0 .. quoted.len() + 2).map(|offset| output_to_input_offset(s, offset)).collect();
+        // Each escape maps back to one input byte; the quotes, and anything past the end, to none.
+        let (newline, byte) = (Some(1), Some(2));
+        let expected = [None, None, Some(0), newline, newline, newline, newline, byte, byte, byte];
+        let expected = [&expected[..], &[byte, Some(3), None, None, None]].concat();
+        assert_eq!(offsets, expected);
+
+        // Copied bytes map back to the same byte in the input.
+        for input in [&b"/bin/cat"[..], b"a b", b"./it's", b"../x\ty z"] {
+            let output = quotemeta(OsStr::from_bytes(input));
+            for (offset, c) in output.bytes().enumerate() {
+                if let Some(index) = output_to_input_offset(input, offset) {
+                    let original = input.get(index).copied();
+                    assert!(original == Some(c) || c == b'\\' || c.is_ascii_digit(), "{:?}", input);
+                }
+            }
+            assert_eq!(output_to_input_offset(input, output.len()), None);
+        }
+        assert_eq!(output_to_input_offset(b"./a b", 1), Some(1));
+        assert_eq!(output_to_input_offset(b"./a b", 2), None);
+        assert_eq!(output_to_input_offset(b"./a b", 3), Some(2));
+        assert_eq!(output_to_input_offset(b"", 0), None);
+    }
+
+    #[cfg(feature = "regex")]
+    #[test]
+    fn test_quotemeta_require_match() {
+        use crate::quotemeta_require_match;
+        use regex::Regex;
+
+        let pattern = Regex::new(r"^[\w.-]+$").expect("valid regex");
+        assert_eq!(quotemeta_require_match(b"notes.txt", &pattern).expect("matches"), "notes.txt");
+        let accented = quotemeta_require_match("caf\u{e9}".as_bytes(), &pattern);
+        assert_eq!(accented.expect("matches"), r"$'caf\303\251'");
+        let violation =
+            quotemeta_require_match(b"a b; rm -rf ~", &pattern).expect_err("does not match");
+        assert_eq!(violation.quoted(), "'a b; rm -rf ~'");
+        assert_eq!(violation.pattern(), r"^[\w.-]+$");
+        assert_eq!(
+            violation.to_string(),
+            r"'a b; rm -rf ~' does not match the allowed pattern /^[\w.-]+$/"
+        );
+        // Invalid UTF-8 never matches, even a pattern which matches anything.
+        let anything = Regex::new("(?s)^.*$").expect("valid regex");
+        assert_eq!(
+            quotemeta_require_match(b"\xff", &anything).expect_err("does not match").quoted(),
+            r"$'\377'"
+        );
+        assert_eq!(quotemeta_require_match(b"", &anything).expect("matches"), "");
+    }
+}
@@ -0,0 +1,86 @@
+//! Escaping for strings which are used as glob or `case` patterns, rather than as data.
+
+#[cfg(unix)] use std::os::unix::ffi::{OsStrExt, OsStringExt};
+use std::{ffi::OsString, path::Path};
+
+/// Returns whether the byte is special within a pattern: the glob characters `*`, `?` and `[`,
+/// the `]` which closes a bracket expression, the `(`, `|` and `)` of bash's extglob patterns, and
+/// the backslash which escapes any of them.
+const fn is_pattern_special(c: u8) -> bool {
+    matches!(c, b'*' | b'?' | b'[' | b']' | b'(' | b'|' | b')' | b'\\')
+}
+
+/// Returns whether the given bytes match only themselves when used as a pattern, so need no
+/// escaping by [`quotemeta_pattern`].
+///
+/// ```
+/// use quotemeta::is_safe_as_pattern;
+///
+/// assert!(is_safe_as_pattern(b"my file.txt"));
+/// assert!(!is_safe_as_pattern(b"*.txt"));
+/// ```
+#[must_use]
+pub fn is_safe_as_pattern(s: &[u8]) -> bool { !s.iter().any(|&c| is_pattern_special(c)) }
+
+/// Escapes the given [`Path`] so that, used as a pattern, it matches exactly itself.
+///
+/// This is for the result of an expansion which is then taken as a pattern, such as `$pat` in
+/// `case $x in $pat)` or `[[ $x == $pat ]]`, or for the pattern given to `find -name`. It is *not*
+/// shell quoting, and the result still needs [`quotemeta`](crate::quotemeta) wherever it is
+/// written into a script. Conversely, writing a pattern directly into a script needs only
+/// [`quotemeta`](crate::quotemeta), since quoted characters in a pattern are always literal.
+///
+/// Each special character is put in a bracket expression of its own, so `*` becomes `[*]` and `[`
+/// becomes `[[]`, since brackets are understood by every implementation of patterns whereas
+/// backslash escapes are not. The exception is the backslash itself, which would start an escape
+/// within the brackets too, and so is written as `\\`. Since `(` and `|` are escaped, the result is
+/// safe whether or not bash's `extglob` option is set. Other bytes are copied as-is.
+///
+/// ```
+/// use quotemeta::quotemeta_pattern;
+///
+/// assert_eq!(quotemeta_pattern("*.txt"), "[*].txt");
+/// assert_eq!(quotemeta_pattern(r"[draft] a\b"), r"[[]draft[]] a\\b");
+/// ```
+pub fn quotemeta_pattern(s: impl AsRef<Path>) -> OsString {
+    let s = s.as_ref().as_os_str().as_bytes();
+    let mut out = Vec::with_capacity(s.len());
+    for &c in s {
+        match c {
+            b'\\' => out.extend_from_slice(br"\\"),
+            _ if is_pattern_special(c) => out.extend_from_slice(&[b'[', c, b']']),
+            _ => out.push(c),
+        }
+    }
+    OsString::from_vec(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{is_safe_as_pattern, quotemeta_pattern};
+    #[cfg(unix)] use std::os::unix::ffi::OsStrExt;
+    use std::ffi::OsStr;
+
+    #[test]
+    fn test_is_safe_as_pattern() {
+        for s in &[&b""[..], b"plain", b"a b", b"it's", b"$x", b"~", b"{a,b}", b"\xff"] {
+            assert!(is_safe_as_pattern(s), "{:?}", s);
+        }
+        for s in &[&b"*"[..], b"a?", b"[abc]", b"]", b"@(a|b)", br"\*", b"!(x)"] {
+            assert!(!is_safe_as_pattern(s), "{:?}", s);
+        }
+    }
+
+    #[test]
+    fn test_quotemeta_pattern() {
+        assert_eq!(quotemeta_pattern(""), "");
+        assert_eq!(quotemeta_pattern("report.txt"), "report.txt");
+        assert_eq!(quotemeta_pattern("a*b"), "a[*]b");
+        assert_eq!(quotemeta_pattern("what?"), "what[?]");
+        assert_eq!(quotemeta_pattern("[x]"), "[[]x[]]");
+        assert_eq!(quotemeta_pattern("@(a|b)"), "@[(]a[|]b[)]");
+        assert_eq!(quotemeta_pattern("!(a)"), "![(]a[)]");
+        assert_eq!(quotemeta_pattern(r"a\b"), r"a\\b");
+        assert_eq!(quotemeta_pattern(OsStr::from_bytes(b"\xff*")), OsStr::from_bytes(b"\xff[*]"));
+    }
+}
@@ -0,0 +1,167 @@
+//! Quoting which matches bash's `printf %q` byte for byte.
+
+#[cfg(unix)] use std::os::unix::ffi::OsStrExt;
+use std::path::Path;
+
+/// Returns whether `printf %q` backslash-escapes the byte wherever it appears in a word.
+const fn is_printf_special(c: u8) -> bool {
+    matches!(
+        c,
+        b' ' | b'!'
+            | b'"'
+            | b'$'
+            | b'&'
+            | b'\''
+            | b'('
+            | b')'
+            | b'*'
+            | b','
+            | b';'
+            | b'<'
+            | b'>'
+            | b'?'
+            | b'['
+            | b'\\'
+            | b']'
+            | b'^'
+            | b'`'
+            | b'{'
+            | b'|'
+            | b'}'
+    )
+}
+
+/// Writes the string in `$'...'` form, as bash's `ansic_quote` does.
+fn write_ansi_c(out: &mut String, s: &[u8]) {
+    out.push_str("$'");
+    for &c in s {
+        let escape = match c {
+            0x1b => 'E',
+            0x07 => 'a',
+            0x08 => 'b',
+            0x0c => 'f',
+            b'\n' => 'n',
+            b'\r' => 'r',
+            b'\t' => 't',
+            0x0b => 'v',
+            b'\\' | b'\'' => char::from(c),
+            b' ' ..= b'~' => {
+                out.push(char::from(c));
+                continue;
+            }
+            _ => {
+                out.push('\\');
+                #[cfg_attr(
+                    feature = "clippy-insane",
+                    expect(clippy::default_numeric_fallback, reason = "shift amounts are obvious")
+                )]
+                for digit in [c >> 6, (c >> 3) & 7, c & 7] {
+                    out.push(char::from(b'0' + digit));
+                }
+                continue;
+            }
+        };
+        out.push('\\');
+        out.push(escape);
+    }
+    out.push('\'');
+}
+
+/// Shell-quotes the given [`Path`] exactly as bash's `printf %q` does in the C locale.
+///
+/// This is useful for checking output against the reference implementation, or for tools whose
+/// users expect to see the same quoting as `printf %q`. Its rules differ from [`quotemeta`]'s in
+/// several ways:
+///
+/// - The empty string is quoted as `''`, rather than left empty.
+/// - Anything which is not printable ASCII causes the whole string to be written as `$'...'`,
+///   using the named escapes `\n`, `\t`, `\E` and so on where they exist and three-digit octal
+///   otherwise. Invalid UTF-8 is therefore escaped byte by byte, as `$'\377'`, and so is valid
+///   UTF-8, since bash only copies multibyte characters through when the locale uses UTF-8.
+/// - Otherwise, each special character is escaped with a backslash, rather than by single-quoting
+///   the whole string, so `a b` becomes `a\ b`.
+/// - A `#` is escaped only at the start of the string, and a `~` only at the start or after a `:`
+///   or `=`, since those are the only places where they are special.
+///
+/// ```
+/// use quotemeta::quotemeta_printf_q;
+///
+/// assert_eq!(&quotemeta_printf_q(""), "''");
+/// assert_eq!(&quotemeta_printf_q("~/my file"), r"\~/my\ file");
+/// assert_eq!(&quotemeta_printf_q("a\nb"), r"$'a\nb'");
+/// ```
+///
+/// [`quotemeta`]: crate::quotemeta
+pub fn quotemeta_printf_q(s: impl AsRef<Path>) -> String {
+    let s = s.as_ref().as_os_str().as_bytes();
+    let mut out = String::with_capacity(s.len() + 3);
+    if s.is_empty() {
+        out.push_str("''");
+    } else if !s.iter().all(|&c| matches!(c, b' ' ..= b'~')) {
+        write_ansi_c(&mut out, s);
+    } else {
+        let mut previous = None;
+        for &c in s {
+            let escape = match c {
+                b'#' => previous.is_none(),
+                b'~' => matches!(previous, None | Some(b':' | b'=')),
+                _ => is_printf_special(c),
+            };
+            if escape {
+                out.push('\\');
+            }
+            out.push(char::from(c));
+            previous = Some(c);
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::quotemeta_printf_q;
+    #[cfg(unix)] use std::os::unix::ffi::OsStrExt;
+    use std::ffi::OsStr;
+
+    // The expected output is from `LC_ALL=C printf %q` in bash 5.2.
+    #[test]
+    fn test_quotemeta_printf_q() {
+        let cases: &[(&[u8], &str)] = &[
+            (b"", "''"),
+            (b"plain", "plain"),
+            (b"~", r"\~"),
+            (b"~~", r"\~~"),
+            (b"a~", "a~"),
+            (b"a:~", r"a:\~"),
+            (b"x=~", r"x=\~"),
+            (b"#x", r"\#x"),
+            (b"a#", "a#"),
+            (b"a b", r"a\ b"),
+            (b"it's", r"it\'s"),
+            (b"{a,b}", r"\{a\,b\}"),
+            (b"-x=%@+:", "-x=%@+:"),
+            (b"a]", r"a\]"),
+            (b"a\nb", r"$'a\nb'"),
+            (b"\x01\x07\x08\x0c\x0b\r\x1b", r"$'\001\a\b\f\v\r\E'"),
+            (b"it's\n", r"$'it\'s\n'"),
+            (b"back\\slash\t", r"$'back\\slash\t'"),
+            (b"\"q\" $x ~\n", "$'\"q\" $x ~\\n'"),
+            (b"\x7f", r"$'\177'"),
+            (b"\xc3\xa9", r"$'\303\251'"),
+            (b"\xff", r"$'\377'"),
+            (b"a\xffb", r"$'a\377b'"),
+        ];
+        for &(s, expected) in cases {
+            assert_eq!(quotemeta_printf_q(OsStr::from_bytes(s)), expected, "{s:?}");
+        }
+    }
+
+    #[test]
+    fn test_printf_q_specials() {
+        // Each printable ASCII character after a letter, as `printf %q` escapes it.
+        let escaped: Vec<u8> = (b' ' ..= b'~')
+            .filter(|&c| quotemeta_printf_q(OsStr::from_bytes(&[b'a', c])).len() > 2)
+            .collect();
+        assert_eq!(escaped, br#" !"$&'()*,;<>?[\]^`{|}"#);
+    }
+}
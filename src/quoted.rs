@@ -0,0 +1,103 @@
+//! A [`Display`](fmt::Display) wrapper which quotes lazily.
+
+use crate::quoter::Quoter;
+#[cfg(unix)] use std::os::unix::ffi::OsStrExt;
+use std::{
+    fmt::{self, Write as _},
+    path::Path,
+};
+
+/// A wrapper whose [`Display`](fmt::Display) implementation writes the shell-quoted form of the
+/// wrapped value.
+///
+/// Quoting happens only when the value is actually formatted, and (unless a width is given) writes
+/// directly to the formatter without building an intermediate `String`. This is useful for
+/// logging, where the message may never be emitted at all. The [`quoted!`](crate::quoted!) macro
+/// is a convenient shorthand for wrapping a borrowed value.
+///
+/// ```
+/// use quotemeta::Quoted;
+///
+/// assert_eq!(format!("cat {}", Quoted::new("my file")), "cat 'my file'");
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Quoted<T>(T);
+
+impl<T: AsRef<Path>> Quoted<T> {
+    /// Wraps the given value.
+    pub const fn new(s: T) -> Self { Self(s) }
+
+    /// Unwraps the value.
+    pub fn into_inner(self) -> T { self.0 }
+}
+
+/// The formatter's width, fill and alignment are applied to the quoted form, so `{:>20}` works as
+/// expected. The precision is ignored, since truncating the quoted form would break it.
+impl<T: AsRef<Path>> fmt::Display for Quoted<T> {
+    #[cfg_attr(
+        feature = "clippy-insane",
+        expect(clippy::integer_division_remainder_used, reason = "centring halves the padding")
+    )]
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let s = self.0.as_ref().as_os_str().as_bytes();
+        let Some(width) = f.width() else { return Quoter::new().write_bytes(s, f) };
+        let quoted = Quoter::new().quote_bytes(s);
+        let padding = width.saturating_sub(quoted.chars().count());
+        let (before, after) = match f.align() {
+            Some(fmt::Alignment::Right) => (padding, 0),
+            Some(fmt::Alignment::Center) => (padding / 2, padding - padding / 2),
+            Some(fmt::Alignment::Left) | None => (0, padding),
+        };
+        let fill = f.fill();
+        (0 .. before).try_for_each(|_| f.write_char(fill))?;
+        f.write_str(&quoted)?;
+        (0 .. after).try_for_each(|_| f.write_char(fill))
+    }
+}
+
+/// Wraps a borrowed value in [`Quoted`], for use as a lazily-quoted argument to `format!`,
+/// `format_args!` and the like.
+///
+/// ```
+/// use quotemeta::quoted;
+///
+/// let path = std::path::PathBuf::from("/tmp/my file");
+/// assert_eq!(format!("rm -- {}", quoted!(path)), "rm -- '/tmp/my file'");
+/// // `path` is merely borrowed, so it is still available afterwards.
+/// assert_eq!(format!("{}", format_args!("ls {}", quoted!(path))), "ls '/tmp/my file'");
+/// ```
+#[macro_export]
+macro_rules! quoted {
+    ($s:expr) => {
+        $crate::Quoted::new(&$s)
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{quotemeta, Quoted};
+    #[cfg(unix)] use std::os::unix::ffi::OsStrExt;
+    use std::ffi::OsStr;
+
+    #[test]
+    fn test_quoted() {
+        let inputs: &[&[u8]] = &[b"", b"test", b"Hello, world!", b"isn't", b"\n3", b"\xa3"];
+        for &s in inputs {
+            let s = OsStr::from_bytes(s);
+            assert_eq!(Quoted::new(s).to_string(), quotemeta(s));
+            assert_eq!(quoted!(s).to_string(), quotemeta(s));
+        }
+        assert_eq!(Quoted::new("x").into_inner(), "x");
+    }
+
+    #[test]
+    fn test_padding() {
+        assert_eq!(format!("{:>20}", Quoted::new("a b")), "               'a b'");
+        assert_eq!(format!("{:<8}|", Quoted::new("a b")), "'a b'   |");
+        assert_eq!(format!("{:*^9}", Quoted::new("a b")), "**'a b'**");
+        assert_eq!(format!("{:8}|", Quoted::new("cat")), "cat     |");
+        // Too narrow to pad, and the precision does not truncate.
+        assert_eq!(format!("{:2.2}", Quoted::new("a b")), "'a b'");
+        assert_eq!(format!("{:>10}", Quoted::new("\n")), r"   $'\012'");
+    }
+}
@@ -0,0 +1,1339 @@
+//! A configurable quoter, for when the defaults of [`quotemeta`](crate::quotemeta) don't suit.
+
+use crate::{is_printable, script::is_identifier, write_c_escaped, Tier, UnrepresentableReason};
+#[cfg(unix)] use std::os::unix::ffi::OsStrExt;
+use std::{
+    borrow::Cow,
+    convert::TryFrom,
+    error::Error,
+    fmt,
+    hash::{Hash, Hasher},
+    iter,
+    path::Path,
+    ptr, str,
+};
+
+/// How a [`Quoter`] divides a string up for quoting.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum QuoteStyle {
+    /// Quote the whole string in the least demanding manner possible. This is the default.
+    #[default]
+    Whole,
+    /// Quote each run of bytes which requires ANSI-C quoting separately, and the text between those
+    /// runs as if it were a string of its own. This keeps the scary-looking `$'...'` segments as
+    /// short as possible, so `long_name\nmore` becomes `long_name$'\012'more` rather than
+    /// `$'long_name\012more'`.
+    IsolateDangerous,
+}
+
+/// How a [`Quoter`] escapes bytes within an ANSI-C quoted string.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum EscapeFormat {
+    /// Escape every byte which needs it as three octal digits, such as `\015`. This is the
+    /// default.
+    #[default]
+    Octal,
+    /// Escape control characters in bash's `\cX` notation where possible: `\cM` for a carriage
+    /// return, `\c@` for NUL, `\c[` for escape and `\c?` for DEL. The exception is 0x1c, which
+    /// would be `\c\` and is parsed differently by different versions of bash. That, and bytes
+    /// which are not control characters, are escaped in octal as usual.
+    CtrlLetter,
+}
+
+impl EscapeFormat {
+    /// Writes a byte to the body of an ANSI-C quoted string, escaping it if necessary.
+    fn write_escaped(self, out: &mut impl fmt::Write, c: u8) -> fmt::Result {
+        match (self, c) {
+            (Self::CtrlLetter, 0 ..= 0x1b | 0x1d ..= 0x1f) => {
+                out.write_str(r"\c")?;
+                out.write_char(char::from(c | 0x40))
+            }
+            (Self::CtrlLetter, 0x7f) => out.write_str(r"\c?"),
+            (Self::Octal | Self::CtrlLetter, _) => write_c_escaped(out, c),
+        }
+    }
+}
+
+/// When a [`Quoter`] quotes a string which would otherwise be left bare, because of how it begins.
+///
+/// Some words mean something different depending on how they start and where they appear, even
+/// though each of their characters is harmless on its own. A policy forces such words to be
+/// quoted. `~` is always quoted regardless, so needs no policy.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum FirstBytePolicy {
+    /// Quote only what the shell requires of an ordinary argument. This is the default.
+    #[default]
+    Argument,
+    /// Also quote words which would have a special meaning in command position: those which look
+    /// like a variable assignment (such as `PATH=/tmp`) and those starting with `=` (which zsh
+    /// expands to the path of a command).
+    Command,
+    /// Also quote words which start with `-`, `=` or a digit, so that options, assignments and
+    /// numeric file descriptors stand out from arguments which merely look like them. Note that
+    /// quoting does not stop a program from taking a word starting with `-` as an option; use
+    /// `--` for that.
+    Conservative,
+}
+
+impl FirstBytePolicy {
+    /// Returns whether the policy forces the string to be quoted.
+    fn forces_quoting(self, s: &[u8]) -> bool {
+        match self {
+            Self::Argument => false,
+            Self::Command => match s.iter().position(|&c| c == b'=') {
+                Some(0) => true,
+                Some(end) => s.get(.. end).is_some_and(is_identifier),
+                None => false,
+            },
+            Self::Conservative => matches!(s.first(), Some(b'-' | b'=' | b'0' ..= b'9')),
+        }
+    }
+}
+
+/// What a [`Quoter`] does with a NUL byte.
+///
+/// A NUL byte cannot be passed to a command at all, since arguments, environment variables and
+/// filenames are C strings which end at the first NUL. bash itself drops NUL bytes from `$'...'`,
+/// or truncates the string there, depending on the version.
+///
+/// Unless a policy is set with [`Quoter::nul_policy`], the default depends on the method: the
+/// byte-safe [`Quoter::try_quote`] uses [`NulPolicy::Error`], and the display-oriented
+/// [`Quoter::quote`] and the other infallible methods use [`NulPolicy::Escape`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum NulPolicy {
+    /// Escape it as `\000`, like any other control character. This suits output which is meant
+    /// for people to read rather than for a shell to run.
+    Escape,
+    /// Refuse to quote the string, so that [`Quoter::try_quote`] returns an error. The infallible
+    /// methods such as [`Quoter::quote`] cannot refuse, so escape it instead.
+    Error,
+    /// Remove NUL bytes from the string before quoting it.
+    Strip,
+    /// Replace each NUL byte with the given byte before quoting the string.
+    Replace(u8),
+}
+
+impl NulPolicy {
+    /// Applies the policy to the string.
+    fn apply(self, s: Cow<'_, [u8]>) -> Result<Cow<'_, [u8]>, UnrepresentableReason> {
+        match s.iter().position(|&c| c == 0) {
+            Some(offset) if self == Self::Error => Err(UnrepresentableReason::Nul(offset)),
+            _ => Ok(self.apply_infallible(s)),
+        }
+    }
+
+    /// Applies the policy to the string, treating [`NulPolicy::Error`] as [`NulPolicy::Escape`].
+    fn apply_infallible(self, s: Cow<'_, [u8]>) -> Cow<'_, [u8]> {
+        if !s.contains(&0) {
+            return s;
+        }
+        match self {
+            Self::Escape | Self::Error => s,
+            Self::Strip => Cow::Owned(s.iter().copied().filter(|&c| c != 0).collect()),
+            Self::Replace(byte) => {
+                Cow::Owned(s.iter().map(|&c| if c == 0 { byte } else { c }).collect())
+            }
+        }
+    }
+}
+
+/// Writes a bare or single-quoted string, which is printable ASCII or (with
+/// [`Quoter::literal_utf8`]) printable UTF-8, and so is copied as-is.
+///
+/// Anything else means the caller got the tier wrong. Release builds then write U+FFFD rather
+/// than mojibake, since the conversion only allocates in that case.
+fn write_verbatim(out: &mut impl fmt::Write, s: &[u8]) -> fmt::Result {
+    debug_assert!(str::from_utf8(s).is_ok(), "verbatim text must be UTF-8");
+    out.write_str(&String::from_utf8_lossy(s))
+}
+
+/// The predicate given to [`Quoter::literal_utf8`].
+///
+/// Function pointers are compared by address, which is not guaranteed to be unique or stable, so
+/// equality is a best effort and the predicate does not contribute to the hash at all.
+#[derive(Clone, Copy, Debug)]
+struct Printable(fn(char) -> bool);
+
+impl PartialEq for Printable {
+    fn eq(&self, other: &Self) -> bool { ptr::fn_addr_eq(self.0, other.0) }
+}
+
+impl Eq for Printable {}
+
+impl Hash for Printable {
+    fn hash<H: Hasher>(&self, _state: &mut H) {}
+}
+
+/// Returns the tier required by a character when printable UTF-8 is emitted as-is.
+fn char_tier(printable: fn(char) -> bool, c: char) -> Tier {
+    match u8::try_from(c) {
+        Ok(byte) if byte.is_ascii() => Tier::of_byte(byte),
+        _ if printable(c) => Tier::Single,
+        _ => Tier::CQuoted,
+    }
+}
+
+/// Writes the body of an ANSI-C quoted string, with printable UTF-8 characters copied as-is.
+fn write_c_escaped_utf8(
+    out: &mut impl fmt::Write, s: &[u8], printable: fn(char) -> bool, format: EscapeFormat,
+) -> fmt::Result {
+    for chunk in s.utf8_chunks() {
+        for c in chunk.valid().chars() {
+            if !c.is_ascii() && printable(c) {
+                out.write_char(c)?;
+            } else {
+                let mut buf = [0; 4];
+                c.encode_utf8(&mut buf)
+                    .bytes()
+                    .try_for_each(|byte| format.write_escaped(out, byte))?;
+            }
+        }
+        chunk.invalid().iter().try_for_each(|&c| format.write_escaped(out, c))?;
+    }
+    Ok(())
+}
+
+/// A Unicode normalization form, for [`Quoter::normalize`].
+#[cfg(feature = "unicode-normalization")]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum Normalization {
+    /// Normalization Form C (canonical composition), as used by most systems.
+    Nfc,
+    /// Normalization Form D (canonical decomposition), as used by macOS filesystems.
+    Nfd,
+}
+
+/// The error returned by [`Quoter::from_spec`] when the spec cannot be parsed.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum SpecError {
+    /// The spec contains a token which is not one of the options.
+    UnknownToken(String),
+    /// The spec contains an option whose value is not valid, such as `escape-limit=x`.
+    InvalidValue(String),
+    /// The spec contains two different tokens which set the same thing.
+    Conflict(String, String),
+}
+
+impl fmt::Display for SpecError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::UnknownToken(token) => write!(f, "unknown quoting option {token:?}"),
+            Self::InvalidValue(token) => write!(f, "invalid value in quoting option {token:?}"),
+            Self::Conflict(first, second) => {
+                write!(f, "quoting options {first:?} and {second:?} conflict")
+            }
+        }
+    }
+}
+
+impl Error for SpecError {}
+
+/// A configurable shell-quoter.
+///
+/// `Quoter::new().quote(s)` is equivalent to `quotemeta(s)`. The various builder methods then
+/// adjust its behaviour:
+///
+/// ```
+/// use quotemeta::Quoter;
+///
+/// let quoter = Quoter::new().c_quote_delimiters("QUOTE(", ")");
+/// assert_eq!(&quoter.quote("it's"), r"QUOTE(it\'s)");
+/// assert_eq!(&quoter.quote("a b"), "'a b'");
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Quoter {
+    single_quote_delimiters: (Cow<'static, str>, Cow<'static, str>),
+    c_quote_delimiters: (Cow<'static, str>, Cow<'static, str>),
+    style: QuoteStyle,
+    printable: Option<Printable>,
+    preserve_dot_prefix: bool,
+    first_byte_policy: FirstBytePolicy,
+    nul_policy: Option<NulPolicy>,
+    escape_format: EscapeFormat,
+    min_tier: Tier,
+    escape_limit: Option<usize>,
+    quote_single_char: bool,
+    #[cfg(feature = "unicode-normalization")]
+    normalization: Option<Normalization>,
+}
+
+impl Default for Quoter {
+    fn default() -> Self { Self::new() }
+}
+
+impl Quoter {
+    /// Creates a quoter with the same behaviour as [`quotemeta`](crate::quotemeta).
+    #[must_use]
+    pub const fn new() -> Self {
+        Self {
+            single_quote_delimiters: (Cow::Borrowed("'"), Cow::Borrowed("'")),
+            c_quote_delimiters: (Cow::Borrowed("$'"), Cow::Borrowed("'")),
+            style: QuoteStyle::Whole,
+            printable: None,
+            preserve_dot_prefix: true,
+            first_byte_policy: FirstBytePolicy::Argument,
+            nul_policy: None,
+            escape_format: EscapeFormat::Octal,
+            min_tier: Tier::Bare,
+            escape_limit: None,
+            quote_single_char: false,
+            #[cfg(feature = "unicode-normalization")]
+            normalization: None,
+        }
+    }
+
+    /// Sets how the string is divided up for quoting. The default is [`QuoteStyle::Whole`].
+    #[must_use]
+    pub const fn style(mut self, style: QuoteStyle) -> Self {
+        self.style = style;
+        self
+    }
+
+    /// Sets the strings which open and close a single-quoted string. The defaults are `'` and `'`.
+    ///
+    /// The delimiters are emitted verbatim, so the output is only safe to pass to a shell if the
+    /// delimiters are too. That is the caller's responsibility.
+    #[must_use]
+    pub fn single_quote_delimiters(
+        mut self, open: impl Into<Cow<'static, str>>, close: impl Into<Cow<'static, str>>,
+    ) -> Self {
+        self.single_quote_delimiters = (open.into(), close.into());
+        self
+    }
+
+    /// Sets the strings which open and close an ANSI-C quoted string. The defaults are `$'` and
+    /// `'`.
+    ///
+    /// This allows the escaping logic to be reused to generate code in some other language, but
+    /// the delimiters are emitted verbatim, so the output is only safe to pass to a shell if the
+    /// delimiters are too. That is the caller's responsibility.
+    #[must_use]
+    pub fn c_quote_delimiters(
+        mut self, open: impl Into<Cow<'static, str>>, close: impl Into<Cow<'static, str>>,
+    ) -> Self {
+        self.c_quote_delimiters = (open.into(), close.into());
+        self
+    }
+
+    /// Emits printable UTF-8 characters as-is, rather than as octal escapes. The default is to
+    /// escape every byte which is not ASCII.
+    ///
+    /// Whether a character counts as printable is decided by the given predicate, since different
+    /// Unicode versions and policies disagree about it. [`is_printable`](crate::is_printable) is
+    /// the policy used by [`quotemeta_display`](crate::quotemeta_display), and rejects control
+    /// characters and invisible or easily-confused ones. Characters which the predicate rejects,
+    /// and bytes which are not valid UTF-8, are escaped as usual. ASCII is unaffected, whatever
+    /// the predicate says.
+    ///
+    /// The output then contains non-ASCII text, which the shell treats as ordinary bytes within
+    /// quotes, but which anything else handling the output must preserve.
+    ///
+    /// ```
+    /// use quotemeta::{is_printable, Quoter};
+    ///
+    /// let quoter = Quoter::new().literal_utf8(is_printable);
+    /// assert_eq!(&quoter.quote("caf\u{e9}"), "'caf\u{e9}'");
+    /// assert_eq!(&quoter.quote("caf\u{e9}\n"), "$'caf\u{e9}\\012'");
+    /// assert_eq!(&quoter.quote("zero\u{200b}width"), r"$'zero\342\200\213width'");
+    /// ```
+    #[must_use]
+    pub const fn literal_utf8(mut self, printable: fn(char) -> bool) -> Self {
+        self.printable = Some(Printable(printable));
+        self
+    }
+
+    /// Sets how bytes are escaped within an ANSI-C quoted string. The default is
+    /// [`EscapeFormat::Octal`].
+    ///
+    /// ```
+    /// use quotemeta::{EscapeFormat, Quoter};
+    ///
+    /// let quoter = Quoter::new().escape_format(EscapeFormat::CtrlLetter);
+    /// assert_eq!(&quoter.quote("line\r\n"), r"$'line\cM\cJ'");
+    /// ```
+    #[must_use]
+    pub const fn escape_format(mut self, format: EscapeFormat) -> Self {
+        self.escape_format = format;
+        self
+    }
+
+    /// Sets when a string is quoted because of how it begins, even if it would otherwise be left
+    /// bare. The default is [`FirstBytePolicy::Argument`].
+    ///
+    /// ```
+    /// use quotemeta::{FirstBytePolicy, Quoter};
+    ///
+    /// let quoter = Quoter::new().first_byte_policy(FirstBytePolicy::Command);
+    /// assert_eq!(&quoter.quote("CC=clang"), "'CC=clang'");
+    /// assert_eq!(&quoter.quote("--cc=clang"), "--cc=clang");
+    /// ```
+    #[must_use]
+    pub const fn first_byte_policy(mut self, policy: FirstBytePolicy) -> Self {
+        self.first_byte_policy = policy;
+        self
+    }
+
+    /// Sets the least tier of quoting to use, escalating beyond it only if the string requires it.
+    /// The default is [`Tier::Bare`].
+    ///
+    /// With [`Tier::Single`], even a safe string is single-quoted, and with [`Tier::CQuoted`],
+    /// everything is ANSI-C quoted. An empty string is then written as `''` or `$''` rather than
+    /// nothing. With [`QuoteStyle::IsolateDangerous`], the tier applies to each run separately.
+    ///
+    /// ```
+    /// use quotemeta::{Quoter, Tier};
+    ///
+    /// let quoter = Quoter::new().min_tier(Tier::Single);
+    /// assert_eq!(&quoter.quote("cat"), "'cat'");
+    /// assert_eq!(&quoter.quote("a\nb"), r"$'a\012b'");
+    /// ```
+    #[must_use]
+    pub const fn min_tier(mut self, tier: Tier) -> Self {
+        self.min_tier = tier;
+        self
+    }
+
+    /// Sets the most escape sequences which [`QuoteStyle::IsolateDangerous`] may emit before it
+    /// gives up and quotes the whole string as one `$'...'`. The default is no limit.
+    ///
+    /// Isolating a few escapes keeps mostly-text strings readable, but a mostly-binary string
+    /// becomes a long jumble of tiny quoted segments which is much harder to read than a single
+    /// `$'...'`. Each byte which must be escaped counts as one, as do `'` and `\` within `$'...'`.
+    /// Other styles already quote the string as a whole, so are unaffected.
+    ///
+    /// ```
+    /// use quotemeta::{QuoteStyle, Quoter};
+    ///
+    /// let quoter = Quoter::new().style(QuoteStyle::IsolateDangerous).escape_limit(2);
+    /// assert_eq!(&quoter.quote("name\r\n"), r"name$'\015\012'");
+    /// assert_eq!(&quoter.quote("\x01a\x02b\x03"), r"$'\001a\002b\003'");
+    /// ```
+    #[must_use]
+    pub const fn escape_limit(mut self, limit: usize) -> Self {
+        self.escape_limit = Some(limit);
+        self
+    }
+
+    /// Sets whether a string of just one byte is always quoted, even if it is safe. The default is
+    /// `false`.
+    ///
+    /// This is purely for consistency in generated scripts, where a lone `a` or `-` can be hard to
+    /// tell apart from the surrounding syntax. A byte which needs more than single quotes still
+    /// gets them.
+    ///
+    /// ```
+    /// use quotemeta::Quoter;
+    ///
+    /// let quoter = Quoter::new().quote_single_char(true);
+    /// assert_eq!(&quoter.quote("a"), "'a'");
+    /// assert_eq!(&quoter.quote("ab"), "ab");
+    /// ```
+    #[must_use]
+    pub const fn quote_single_char(mut self, quote: bool) -> Self {
+        self.quote_single_char = quote;
+        self
+    }
+
+    /// Sets whether the output must be safe for a script which has run `shopt -s extglob`. The
+    /// default is `false`.
+    ///
+    /// This has no effect, because the output is always safe with extglob: each of its patterns,
+    /// such as `!(foo)` or `+(foo)`, contains a `(`, which is never left bare. The option exists so
+    /// that callers can state the assumption in code.
+    ///
+    /// ```
+    /// use quotemeta::Quoter;
+    ///
+    /// let quoter = Quoter::new().assume_extglob(true);
+    /// assert_eq!(&quoter.quote("!(foo)"), "'!(foo)'");
+    /// assert_eq!(quoter, Quoter::new());
+    /// ```
+    #[must_use]
+    pub const fn assume_extglob(self, _assume: bool) -> Self { self }
+
+    /// Sets what to do with a NUL byte, whichever method is used to quote the string.
+    ///
+    /// By default, [`Quoter::try_quote`] refuses a NUL byte, as for [`NulPolicy::Error`], while
+    /// [`Quoter::quote`] and the other infallible methods escape it, as for [`NulPolicy::Escape`]
+    /// and [`quotemeta`](crate::quotemeta). Since they cannot fail, those methods escape a NUL byte
+    /// even under [`NulPolicy::Error`].
+    ///
+    /// ```
+    /// use quotemeta::{NulPolicy, Quoter};
+    ///
+    /// assert_eq!(&Quoter::new().nul_policy(NulPolicy::Strip).quote("a\0b"), "ab");
+    /// assert_eq!(&Quoter::new().nul_policy(NulPolicy::Replace(b'_')).quote("a\0b"), "a_b");
+    /// let escape = Quoter::new().nul_policy(NulPolicy::Escape);
+    /// assert_eq!(escape.try_quote("\0").as_deref(), Ok(r"$'\000'"));
+    /// ```
+    #[must_use]
+    pub const fn nul_policy(mut self, policy: NulPolicy) -> Self {
+        self.nul_policy = Some(policy);
+        self
+    }
+
+    /// Sets whether a leading `./` or `../` (or several, as in `../../`) is written as-is ahead of
+    /// the quoted remainder of the string, so that `./weird name` becomes `./'weird name'`. The
+    /// default is `true`; if `false`, it is quoted along with the rest, as `'./weird name'`.
+    ///
+    /// Either way, the prefix is never removed or altered, and is always kept as part of the same
+    /// shell word. This takes effect only when the rest of the string needs quoting.
+    ///
+    /// ```
+    /// use quotemeta::Quoter;
+    ///
+    /// assert_eq!(&Quoter::new().quote("../my file"), "../'my file'");
+    /// assert_eq!(&Quoter::new().preserve_dot_prefix(false).quote("../my file"), "'../my file'");
+    /// ```
+    #[must_use]
+    pub const fn preserve_dot_prefix(mut self, preserve: bool) -> Self {
+        self.preserve_dot_prefix = preserve;
+        self
+    }
+
+    /// Sets the Unicode normalization form to convert the input to before it is quoted. The default
+    /// is to leave the input alone.
+    ///
+    /// This means that a filename which has been decomposed by macOS quotes to the same output as
+    /// the composed form typed by a user, but be aware that the output then represents different
+    /// bytes to the input. Only valid UTF-8 is normalized; any other bytes are left untouched.
+    ///
+    /// This requires the `unicode-normalization` feature.
+    ///
+    /// ```
+    /// use quotemeta::{Normalization, Quoter};
+    ///
+    /// let quoter = Quoter::new().normalize(Normalization::Nfc);
+    /// assert_eq!(&quoter.quote("e\u{301}"), r"$'\303\251'");
+    /// ```
+    #[cfg(feature = "unicode-normalization")]
+    #[must_use]
+    pub const fn normalize(mut self, normalization: Normalization) -> Self {
+        self.normalization = Some(normalization);
+        self
+    }
+
+    /// Creates a quoter from a compact spec, such as one given in a command-line option.
+    ///
+    /// The spec is a comma-separated list of tokens, each of which calls one of the builder
+    /// methods, applied to [`Quoter::new`]. Whitespace around a token is ignored, and an empty
+    /// spec gives the default quoter. The tokens are:
+    ///
+    /// | Token | Builder call |
+    /// |-------|--------------|
+    /// | `whole`, `isolate-dangerous` | [`Quoter::style`] |
+    /// | `octal`, `ctrl-letter` | [`Quoter::escape_format`] |
+    /// | `argument`, `command`, `conservative` | [`Quoter::first_byte_policy`] |
+    /// | `nul-escape`, `nul-error`, `nul-strip`, `nul-replace=C` | [`Quoter::nul_policy`] |
+    /// | `bare`, `always-single`, `always-c-quoted` | [`Quoter::min_tier`] |
+    /// | `escape-limit=N` | [`Quoter::escape_limit`] |
+    /// | `quote-single-char` | [`Quoter::quote_single_char`] |
+    /// | `dot-prefix`, `no-dot-prefix` | [`Quoter::preserve_dot_prefix`] |
+    /// | `literal-utf8` | [`Quoter::literal_utf8`], with [`is_printable`] |
+    /// | `nfc`, `nfd` | `Quoter::normalize`, with the `unicode-normalization` feature |
+    ///
+    /// The value of `nul-replace` is the single ASCII character to replace NUL bytes with, and
+    /// that of `escape-limit` a decimal number. Each row sets one thing, so giving two different
+    /// tokens from the same row is an error, though repeating a token is allowed.
+    ///
+    /// # Errors
+    ///
+    /// Returns a [`SpecError`] if a token is unknown or has an invalid value, or if two tokens
+    /// conflict.
+    ///
+    /// ```
+    /// use quotemeta::{Quoter, QuoteStyle, SpecError, Tier};
+    ///
+    /// let quoter = Quoter::from_spec("isolate-dangerous,always-single").unwrap();
+    /// let expected = Quoter::new().style(QuoteStyle::IsolateDangerous).min_tier(Tier::Single);
+    /// assert_eq!(quoter, expected);
+    /// assert_eq!(&quoter.quote("a\nb"), r"'a'$'\012''b'");
+    /// assert_eq!(
+    ///     Quoter::from_spec("octal,ctrl-letter"),
+    ///     Err(SpecError::Conflict(String::from("octal"), String::from("ctrl-letter")))
+    /// );
+    /// ```
+    pub fn from_spec(spec: &str) -> Result<Self, SpecError> {
+        let (mut quoter, mut seen) = (Self::new(), Vec::<(&str, &str)>::new());
+        if spec.trim().is_empty() {
+            return Ok(quoter);
+        }
+        for token in spec.split(',').map(str::trim) {
+            let (setting, configured) = quoter.apply_spec_token(token)?;
+            match seen.iter().find(|&&(previous, _)| previous == setting) {
+                Some(&(_, other)) if other != token => {
+                    return Err(SpecError::Conflict(other.to_owned(), token.to_owned()));
+                }
+                Some(_) => {}
+                None => seen.push((setting, token)),
+            }
+            quoter = configured;
+        }
+        Ok(quoter)
+    }
+
+    /// Applies one token of a spec for [`Quoter::from_spec`], and returns the setting it changed
+    /// along with the reconfigured quoter.
+    fn apply_spec_token(self, token: &str) -> Result<(&'static str, Self), SpecError> {
+        let invalid = || SpecError::InvalidValue(token.to_owned());
+        let (name, value) = match token.split_once('=') {
+            Some((name, value)) => (name, Some(value)),
+            None => (token, None),
+        };
+        Ok(match (name, value) {
+            ("whole", None) => ("style", self.style(QuoteStyle::Whole)),
+            ("isolate-dangerous", None) => ("style", self.style(QuoteStyle::IsolateDangerous)),
+            ("octal", None) => ("escape format", self.escape_format(EscapeFormat::Octal)),
+            ("ctrl-letter", None) => {
+                ("escape format", self.escape_format(EscapeFormat::CtrlLetter))
+            }
+            ("argument", None) => ("first byte", self.first_byte_policy(FirstBytePolicy::Argument)),
+            ("command", None) => ("first byte", self.first_byte_policy(FirstBytePolicy::Command)),
+            ("conservative", None) => {
+                ("first byte", self.first_byte_policy(FirstBytePolicy::Conservative))
+            }
+            ("nul-escape", None) => ("NUL", self.nul_policy(NulPolicy::Escape)),
+            ("nul-error", None) => ("NUL", self.nul_policy(NulPolicy::Error)),
+            ("nul-strip", None) => ("NUL", self.nul_policy(NulPolicy::Strip)),
+            ("nul-replace", Some(byte)) => match byte.as_bytes() {
+                &[c] if c.is_ascii() => ("NUL", self.nul_policy(NulPolicy::Replace(c))),
+                _ => return Err(invalid()),
+            },
+            ("bare", None) => ("tier", self.min_tier(Tier::Bare)),
+            ("always-single", None) => ("tier", self.min_tier(Tier::Single)),
+            ("always-c-quoted", None) => ("tier", self.min_tier(Tier::CQuoted)),
+            ("escape-limit", Some(limit)) => {
+                ("escape limit", self.escape_limit(limit.parse().ok().ok_or_else(invalid)?))
+            }
+            ("quote-single-char", None) => ("single char", self.quote_single_char(true)),
+            ("dot-prefix", None) => ("dot prefix", self.preserve_dot_prefix(true)),
+            ("no-dot-prefix", None) => ("dot prefix", self.preserve_dot_prefix(false)),
+            ("literal-utf8", None) => ("literal UTF-8", self.literal_utf8(is_printable)),
+            #[cfg(feature = "unicode-normalization")]
+            ("nfc", None) => ("normalization", self.normalize(Normalization::Nfc)),
+            #[cfg(feature = "unicode-normalization")]
+            ("nfd", None) => ("normalization", self.normalize(Normalization::Nfd)),
+            _ => return Err(SpecError::UnknownToken(token.to_owned())),
+        })
+    }
+
+    /// Shell-quotes the given [`Path`].
+    ///
+    /// This takes any `AsRef<Path>` in the same manner as [`quotemeta`](crate::quotemeta). It is
+    /// meant for display, so a NUL byte is escaped unless the [`NulPolicy`] says otherwise; use
+    /// [`Quoter::try_quote`] where a NUL byte should be refused instead.
+    pub fn quote(&self, s: impl AsRef<Path>) -> String {
+        self.quote_bytes(&self.prepared_infallible(s.as_ref().as_os_str().as_bytes()))
+    }
+
+    /// Shell-quotes the given [`Path`], or returns an error if the string contains a NUL byte and
+    /// the [`NulPolicy`] is [`NulPolicy::Error`], as it is by default for this method.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`UnrepresentableReason::Nul`] as described.
+    ///
+    /// ```
+    /// use quotemeta::{NulPolicy, Quoter, UnrepresentableReason};
+    ///
+    /// let quoter = Quoter::new();
+    /// assert_eq!(quoter.try_quote("a b").as_deref(), Ok("'a b'"));
+    /// assert_eq!(quoter.try_quote("a\0b"), Err(UnrepresentableReason::Nul(1)));
+    /// let stripping = Quoter::new().nul_policy(NulPolicy::Strip);
+    /// assert_eq!(stripping.try_quote("a\0b").as_deref(), Ok("ab"));
+    /// ```
+    pub fn try_quote(&self, s: impl AsRef<Path>) -> Result<String, UnrepresentableReason> {
+        let s = self.prepared(s.as_ref().as_os_str().as_bytes(), false)?;
+        Ok(self.quote_bytes(&s))
+    }
+
+    /// Shell-quotes the given bytes as [`Quoter::try_quote`] does, except that
+    /// [`NulPolicy::Escape`] is treated as [`NulPolicy::Error`].
+    pub(crate) fn try_quote_strict(&self, s: &[u8]) -> Result<String, UnrepresentableReason> {
+        Ok(self.quote_bytes(&self.prepared(s, true)?))
+    }
+
+    /// Appends the quoted form of the given [`Path`] to a command line which is being built up.
+    ///
+    /// A space is added first, unless `out` is empty or already ends with whitespace. An empty
+    /// string is written as `''`, so that it is not lost. A NUL byte is treated as for
+    /// [`Quoter::quote`].
+    ///
+    /// ```
+    /// use quotemeta::Quoter;
+    ///
+    /// let (quoter, mut command) = (Quoter::new(), String::new());
+    /// for arg in &["cp", "my file", "/tmp/"] {
+    ///     quoter.append_arg(&mut command, arg);
+    /// }
+    /// assert_eq!(&command, "cp 'my file' /tmp/");
+    /// ```
+    pub fn append_arg(&self, out: &mut String, s: impl AsRef<Path>) {
+        let s = self.prepared_infallible(s.as_ref().as_os_str().as_bytes());
+        if !out.is_empty() && !out.ends_with(char::is_whitespace) {
+            out.push(' ');
+        }
+        if s.is_empty() {
+            out.push_str("''");
+        } else {
+            out.push_str(&self.quote_bytes(&s));
+        }
+    }
+
+    /// Applies the configured Unicode normalization and [`NulPolicy`] to the string, as
+    /// [`Quoter::try_quote`] does. If `strict` is set, [`NulPolicy::Escape`] is treated as
+    /// [`NulPolicy::Error`].
+    fn prepared<'input>(
+        &self, s: &'input [u8], strict: bool,
+    ) -> Result<Cow<'input, [u8]>, UnrepresentableReason> {
+        let policy = match self.nul_policy {
+            Some(NulPolicy::Escape) if strict => NulPolicy::Error,
+            Some(policy) => policy,
+            None => NulPolicy::Error,
+        };
+        policy.apply(self.normalized(s))
+    }
+
+    /// Applies the configured Unicode normalization and [`NulPolicy`] to the string, as
+    /// [`Quoter::quote`] does, escaping a NUL byte rather than refusing it.
+    pub(crate) fn prepared_infallible<'input>(&self, s: &'input [u8]) -> Cow<'input, [u8]> {
+        self.nul_policy.unwrap_or(NulPolicy::Escape).apply_infallible(self.normalized(s))
+    }
+
+    /// Applies the configured Unicode normalization to the valid UTF-8 parts of the string.
+    #[cfg(not(feature = "unicode-normalization"))]
+    #[cfg_attr(feature = "clippy-insane", expect(clippy::unused_self))] //// as with the feature
+    const fn normalized<'input>(&self, s: &'input [u8]) -> Cow<'input, [u8]> { Cow::Borrowed(s) }
+
+    /// Applies the configured Unicode normalization to the valid UTF-8 parts of the string.
+    #[cfg(feature = "unicode-normalization")]
+    fn normalized<'input>(&self, s: &'input [u8]) -> Cow<'input, [u8]> {
+        use unicode_normalization::{is_nfc, is_nfd, UnicodeNormalization as _};
+
+        let Some(normalization) = self.normalization else { return Cow::Borrowed(s) };
+        let is_normalized = |text: &str| match normalization {
+            Normalization::Nfc => is_nfc(text),
+            Normalization::Nfd => is_nfd(text),
+        };
+        if s.utf8_chunks().all(|chunk| is_normalized(chunk.valid())) {
+            return Cow::Borrowed(s);
+        }
+        let mut out = Vec::with_capacity(s.len());
+        for chunk in s.utf8_chunks() {
+            let text: String = match normalization {
+                Normalization::Nfc => chunk.valid().nfc().collect(),
+                Normalization::Nfd => chunk.valid().nfd().collect(),
+            };
+            out.extend_from_slice(text.as_bytes());
+            out.extend_from_slice(chunk.invalid());
+        }
+        Cow::Owned(out)
+    }
+
+    #[cfg_attr(
+        feature = "clippy-insane",
+        expect(clippy::expect_used, reason = "writing to a `String` cannot fail")
+    )]
+    pub(crate) fn quote_bytes(&self, s: &[u8]) -> String {
+        let mut out = String::with_capacity(s.len() + 3);
+        self.write_bytes(s, &mut out).expect("writing to a String cannot fail");
+        out
+    }
+
+    /// Writes the quoted form of the string.
+    pub(crate) fn write_bytes(&self, s: &[u8], out: &mut impl fmt::Write) -> fmt::Result {
+        match self.style {
+            QuoteStyle::Whole => self.write_bytes_as(s, self.tier_of(s).max(self.floor(s)), out),
+            QuoteStyle::IsolateDangerous => {
+                let (prefix, body) = self.split_dot_prefix(s);
+                let tiers = self.byte_tiers(body);
+                let escapes = tiers.iter().filter(|&&tier| tier == Tier::CQuoted).count();
+                if self.escape_limit.is_some_and(|limit| escapes > limit) {
+                    return self.write_bytes_as(s, Tier::CQuoted.max(self.floor(s)), out);
+                }
+                write_verbatim(out, prefix)?;
+                if body.is_empty() {
+                    return self.write_run(b"", self.floor(s), out);
+                }
+                let runs = tiers.chunk_by(|&a, &b| (a == Tier::CQuoted) == (b == Tier::CQuoted));
+                let (mut rest, mut floor) = (body, self.floor(s));
+                for run in runs {
+                    let (head, tail) = rest.split_at(run.len());
+                    let tier = run.iter().copied().max().unwrap_or(Tier::Bare).max(floor);
+                    self.write_run(head, tier, out)?;
+                    (rest, floor) = (tail, self.min_tier);
+                }
+                Ok(())
+            }
+        }
+    }
+
+    /// Returns the least tier which the [`FirstBytePolicy`], [`Quoter::min_tier`] and
+    /// [`Quoter::quote_single_char`] allow for the string.
+    fn floor(&self, s: &[u8]) -> Tier {
+        let forced = self.first_byte_policy.forces_quoting(s)
+            || (self.quote_single_char && s.len() == 1);
+        let policy = if forced { Tier::Single } else { Tier::Bare };
+        policy.max(self.min_tier)
+    }
+
+    /// Splits off the leading `./` and `../` components which [`Quoter::preserve_dot_prefix`]
+    /// keeps outside the quotes.
+    pub(crate) fn split_dot_prefix<'input>(&self, s: &'input [u8]) -> (&'input [u8], &'input [u8]) {
+        if !self.preserve_dot_prefix {
+            return (&[], s);
+        }
+        let mut rest = s;
+        while let Some(tail) = rest.strip_prefix(b"./").or_else(|| rest.strip_prefix(b"../")) {
+            rest = tail;
+        }
+        s.split_at(s.len() - rest.len())
+    }
+
+    /// Returns the tier required by each byte of the string. Every byte of a multi-byte character
+    /// has the tier of the character as a whole.
+    fn byte_tiers(&self, s: &[u8]) -> Vec<Tier> {
+        let Some(Printable(printable)) = self.printable else {
+            return s.iter().map(|&c| Tier::of_byte(c)).collect();
+        };
+        let mut tiers = Vec::with_capacity(s.len());
+        for chunk in s.utf8_chunks() {
+            for c in chunk.valid().chars() {
+                tiers.extend(iter::repeat_n(char_tier(printable, c), c.len_utf8()));
+            }
+            tiers.extend(iter::repeat_n(Tier::CQuoted, chunk.invalid().len()));
+        }
+        tiers
+    }
+
+    /// Returns the tier required by the string, taking [`Quoter::literal_utf8`] into account.
+    fn tier_of(&self, s: &[u8]) -> Tier {
+        if self.printable.is_none() {
+            return Tier::of(s);
+        }
+        self.byte_tiers(s).into_iter().max().unwrap_or(Tier::Bare)
+    }
+
+    /// Quotes the string using the given tier, which must be at least [`Tier::of`] the string.
+    #[cfg_attr(
+        feature = "clippy-insane",
+        expect(clippy::expect_used, reason = "writing to a `String` cannot fail")
+    )]
+    pub(crate) fn quote_bytes_as(&self, s: &[u8], tier: Tier) -> String {
+        let mut out = String::with_capacity(s.len() + 3);
+        self.write_bytes_as(s, tier, &mut out).expect("writing to a String cannot fail");
+        out
+    }
+
+    /// Writes the string quoted using the given tier, which must be at least [`Tier::of`] the
+    /// string.
+    pub(crate) fn write_bytes_as(
+        &self, s: &[u8], tier: Tier, out: &mut impl fmt::Write,
+    ) -> fmt::Result {
+        match self.split_dot_prefix(s) {
+            (prefix, rest) if tier > Tier::Bare && !prefix.is_empty() && !rest.is_empty() => {
+                write_verbatim(out, prefix)?;
+                self.write_run(rest, tier, out)
+            }
+            _ => self.write_run(s, tier, out),
+        }
+    }
+
+    /// Writes a run of bytes quoted using the given tier, ignoring any dot prefix.
+    fn write_run(&self, s: &[u8], tier: Tier, out: &mut impl fmt::Write) -> fmt::Result {
+        match tier {
+            Tier::Bare => write_verbatim(out, s),
+            Tier::Single => {
+                let (open, close) = &self.single_quote_delimiters;
+                out.write_str(open)?;
+                write_verbatim(out, s)?;
+                out.write_str(close)
+            }
+            Tier::CQuoted => {
+                let (open, close) = &self.c_quote_delimiters;
+                let format = self.escape_format;
+                out.write_str(open)?;
+                match self.printable {
+                    None => s.iter().try_for_each(|&c| format.write_escaped(out, c))?,
+                    Some(Printable(printable)) => {
+                        write_c_escaped_utf8(out, s, printable, format)?;
+                    }
+                }
+                out.write_str(close)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        is_printable, quotemeta, tests::shell_words, EscapeFormat, FirstBytePolicy, NulPolicy,
+        QuoteStyle, Quoter, SpecError, Tier, UnrepresentableReason,
+    };
+    #[cfg(unix)] use std::os::unix::ffi::OsStrExt;
+    use std::ffi::OsStr;
+
+    #[test]
+    fn test_default() {
+        for s in &["", "test", "Hello, world!", "isn't", "\n3", "\u{a3}"] {
+            assert_eq!(Quoter::new().quote(s), quotemeta(s));
+            assert_eq!(Quoter::default().quote(s), quotemeta(s));
+        }
+    }
+
+    #[test]
+    fn test_delimiters() {
+        let quoter =
+            Quoter::new().single_quote_delimiters("<", ">").c_quote_delimiters("QUOTE(", ")");
+        // Bare strings have no delimiters to change.
+        assert_eq!(&quoter.quote("test"), "test");
+        assert_eq!(&quoter.quote("Hello, world!"), "<Hello, world!>");
+        // The body is still escaped as before.
+        assert_eq!(&quoter.quote("isn't"), r"QUOTE(isn\'t)");
+        assert_eq!(&quoter.quote("a\\b\n"), r"QUOTE(a\\b\012)");
+        assert_eq!(&quoter.quote("\u{a3}"), r"QUOTE(\302\243)");
+        // Owned delimiters are also accepted.
+        let owned = Quoter::new().c_quote_delimiters(String::from("E'"), String::from("'"));
+        assert_eq!(&owned.quote("\t"), r"E'\011'");
+    }
+
+    #[test]
+    fn test_isolate_dangerous() {
+        let quoter = Quoter::new().style(QuoteStyle::IsolateDangerous);
+        assert_eq!(&quoter.quote(""), "");
+        assert_eq!(&quoter.quote("test"), "test");
+        assert_eq!(&quoter.quote("Hello, world"), "'Hello, world'");
+        assert_eq!(&quoter.quote("long_safe_name\nmore_safe"), r"long_safe_name$'\012'more_safe");
+        assert_eq!(&quoter.quote("a b\r\nc d"), r"'a b'$'\015\012''c d'");
+        assert_eq!(&quoter.quote("isn't"), r"isn$'\''t");
+        assert_eq!(&quoter.quote("\u{a3}5"), r"$'\302\243'5");
+        assert_eq!(&quoter.quote("\n"), r"$'\012'");
+
+        let inputs: &[&[u8]] = &[b"a\nb", b"a b\tc d", b"it's", b"\xff\xfe", b"x\x00 y\\z"];
+        for &s in inputs {
+            assert_eq!(shell_words(&quoter.quote(OsStr::from_bytes(s))), [s]);
+        }
+    }
+
+    #[test]
+    fn test_escape_format() {
+        let quoter = Quoter::new().escape_format(EscapeFormat::CtrlLetter);
+        assert_eq!(&quoter.quote("\r"), r"$'\cM'");
+        assert_eq!(&quoter.quote("\0"), r"$'\c@'");
+        assert_eq!(&quoter.quote("\x1b[0m\x7f"), r"$'\c[[0m\c?'");
+        assert_eq!(&quoter.quote("\x1c\x1d\x1f"), r"$'\034\c]\c_'");
+        assert_eq!(&quoter.quote("\u{a3}"), r"$'\302\243'");
+        assert_eq!(&quoter.quote(OsStr::from_bytes(b"\xff\t")), r"$'\377\cI'");
+        assert_eq!(&quoter.quote(r"it's a\b"), r"$'it\'s a\\b'");
+        // ASCII letters after a `\cX` are not mistaken for part of it.
+        assert_eq!(&quoter.quote("\nM"), r"$'\cJM'");
+        let utf8 = quoter.literal_utf8(is_printable);
+        assert_eq!(&utf8.quote("\u{e9}\u{85}\n"), "$'\u{e9}\\302\\205\\cJ'");
+    }
+
+    #[test]
+    fn test_append_arg() {
+        let quoter = Quoter::new();
+        let mut out = String::new();
+        quoter.append_arg(&mut out, "echo");
+        assert_eq!(&out, "echo");
+        quoter.append_arg(&mut out, "");
+        quoter.append_arg(&mut out, "a b");
+        assert_eq!(&out, "echo '' 'a b'");
+        assert_eq!(shell_words(&out), [&b"echo"[..], b"", b"a b"]);
+        // No extra space is added after existing whitespace.
+        let mut line = String::from("sudo ");
+        quoter.append_arg(&mut line, "it's");
+        assert_eq!(&line, r"sudo $'it\'s'");
+    }
+
+    #[test]
+    fn test_first_byte_policy() {
+        let argument = Quoter::new();
+        let command = Quoter::new().first_byte_policy(FirstBytePolicy::Command);
+        let conservative = Quoter::new().first_byte_policy(FirstBytePolicy::Conservative);
+        let cases = [
+            // input, Argument, Command, Conservative
+            ("-rf", "-rf", "-rf", "'-rf'"),
+            ("~user", "'~user'", "'~user'", "'~user'"),
+            ("=ls", "=ls", "'=ls'", "'=ls'"),
+            ("VAR=x", "VAR=x", "'VAR=x'", "VAR=x"),
+            ("2", "2", "2", "'2'"),
+            ("a-b=c", "a-b=c", "a-b=c", "a-b=c"),
+            ("", "", "", ""),
+            ("-a b", "'-a b'", "'-a b'", "'-a b'"),
+            ("-\n", r"$'-\012'", r"$'-\012'", r"$'-\012'"),
+        ];
+        for &(s, expect_argument, expect_command, expect_conservative) in &cases {
+            assert_eq!(argument.quote(s), expect_argument);
+            assert_eq!(command.quote(s), expect_command);
+            assert_eq!(conservative.quote(s), expect_conservative);
+        }
+        // Only the first run is forced when isolating dangerous bytes.
+        let isolated = conservative.style(QuoteStyle::IsolateDangerous);
+        assert_eq!(&isolated.quote("-x\n-y"), r"'-x'$'\012'-y");
+    }
+
+    #[test]
+    fn test_min_tier() {
+        let single = Quoter::new().min_tier(Tier::Single);
+        assert_eq!(&single.quote("cat"), "'cat'");
+        assert_eq!(&single.quote(""), "''");
+        assert_eq!(&single.quote("a b"), "'a b'");
+        assert_eq!(&single.quote("a\nb"), r"$'a\012b'");
+        assert_eq!(&single.quote("./cat"), "./'cat'");
+        let c_quoted = Quoter::new().min_tier(Tier::CQuoted);
+        assert_eq!(&c_quoted.quote("cat"), "$'cat'");
+        assert_eq!(&c_quoted.quote("a b"), "$'a b'");
+        assert_eq!(&c_quoted.quote("a\nb"), r"$'a\012b'");
+        assert_eq!(&Quoter::new().min_tier(Tier::Bare).quote("cat"), "cat");
+        // Every run is raised to the floor when isolating dangerous bytes.
+        let isolated = single.style(QuoteStyle::IsolateDangerous);
+        assert_eq!(&isolated.quote("x\ny"), r"'x'$'\012''y'");
+        // An empty string is still quoted, whichever the style.
+        for style in [QuoteStyle::Whole, QuoteStyle::IsolateDangerous] {
+            let quoter = |tier| Quoter::new().style(style).min_tier(tier);
+            assert_eq!(&quoter(Tier::Single).quote(""), "''");
+            assert_eq!(&quoter(Tier::CQuoted).quote(""), "$''");
+            assert_eq!(&quoter(Tier::Bare).quote(""), "");
+        }
+    }
+
+    #[test]
+    fn test_escape_limit() {
+        let isolated = Quoter::new().style(QuoteStyle::IsolateDangerous);
+        let limited = isolated.clone().escape_limit(3);
+        let cases = [
+            // input, isolated with a limit of 3
+            ("plain", "plain"),
+            ("a b", "'a b'"),
+            ("file\n", r"file$'\012'"),
+            ("a\nb\tc\r", r"a$'\012'b$'\011'c$'\015'"),
+            ("a\nb\tc\rd\x1b", r"$'a\012b\011c\015d\033'"),
+            ("it's a\n", r"it$'\'''s a'$'\012'"),
+            ("it's\\ a\n\\", r"$'it\'s\\ a\012\\'"),
+            ("it's\n", r"it$'\''s$'\012'"),
+            ("./\x01\x02\x03\x04 x", r"./$'\001\002\003\004 x'"),
+        ];
+        for &(s, expected) in &cases {
+            assert_eq!(limited.quote(s), expected, "{s:?}");
+        }
+        assert_eq!(&isolated.quote("a\nb\tc\rd\x1b"), r"a$'\012'b$'\011'c$'\015'd$'\033'");
+        assert_eq!(&isolated.escape_limit(0).quote("a b\n"), r"$'a b\012'");
+        // Whole-string quoting is unaffected.
+        assert_eq!(&Quoter::new().escape_limit(0).quote("a\nb"), r"$'a\012b'");
+    }
+
+    #[test]
+    fn test_quote_single_char() {
+        let (default, quoted) = (Quoter::new(), Quoter::new().quote_single_char(true));
+        let cases = [
+            // input, default, quote_single_char
+            ("a", "a", "'a'"),
+            ("-", "-", "'-'"),
+            (">", "'>'", "'>'"),
+            ("\n", r"$'\012'", r"$'\012'"),
+            ("", "", ""),
+            ("ab", "ab", "ab"),
+            ("--", "--", "--"),
+        ];
+        for &(s, expect_default, expect_quoted) in &cases {
+            assert_eq!(default.quote(s), expect_default);
+            assert_eq!(quoted.quote(s), expect_quoted);
+        }
+        assert_eq!(&Quoter::new().quote_single_char(false).quote("a"), "a");
+    }
+
+    #[test]
+    fn test_assume_extglob() {
+        let (default, extglob) = (Quoter::new(), Quoter::new().assume_extglob(true));
+        assert_eq!(&extglob.quote("!(foo)"), "'!(foo)'");
+        assert_eq!(&default.quote("!(foo)"), "'!(foo)'");
+        for s in &["?(a|b)", "*(x)", "+(x)", "@(x)", "file+(1).txt", "a+b@c", "a!b", "a b", "\n"] {
+            assert_eq!(extglob.quote(s), default.quote(s), "{s}");
+            assert_eq!(extglob.quote(s), quotemeta(s), "{s}");
+        }
+        assert_eq!(Quoter::new().assume_extglob(false), default);
+    }
+
+    #[test]
+    fn test_nul_policy() {
+        let input = "a\0b c";
+        let quoter = |policy| Quoter::new().nul_policy(policy);
+        assert_eq!(&Quoter::new().quote(input), r"$'a\000b c'");
+        assert_eq!(&quoter(NulPolicy::Escape).quote(input), r"$'a\000b c'");
+        assert_eq!(quoter(NulPolicy::Error).try_quote(input), Err(UnrepresentableReason::Nul(1)));
+        assert_eq!(quoter(NulPolicy::Error).try_quote("a b").as_deref(), Ok("'a b'"));
+        assert_eq!(&quoter(NulPolicy::Strip).quote(input), "'ab c'");
+        assert_eq!(&quoter(NulPolicy::Strip).quote("\0\0"), "");
+        assert_eq!(&quoter(NulPolicy::Replace(b'_')).quote(input), "'a_b c'");
+        assert_eq!(&quoter(NulPolicy::Replace(b'\n')).quote(input), r"$'a\012b c'");
+
+        assert_eq!(quoter(NulPolicy::Strip).try_quote(input).as_deref(), Ok("'ab c'"));
+        assert_eq!(quoter(NulPolicy::Replace(b'_')).try_quote(input).as_deref(), Ok("'a_b c'"));
+
+        let mut command = String::from("echo");
+        quoter(NulPolicy::Strip).append_arg(&mut command, "\0");
+        assert_eq!(&command, "echo ''");
+    }
+
+    #[test]
+    fn test_nul_policy_defaults() {
+        let input = "a\0b c";
+        // The byte-safe API refuses a NUL byte unless told otherwise.
+        assert_eq!(Quoter::new().try_quote(input), Err(UnrepresentableReason::Nul(1)));
+        let escape = Quoter::new().nul_policy(NulPolicy::Escape);
+        assert_eq!(escape.try_quote(input).as_deref(), Ok(r"$'a\000b c'"));
+        // The display-oriented API escapes it, and never panics.
+        let error = Quoter::new().nul_policy(NulPolicy::Error);
+        assert_eq!(&error.quote(input), r"$'a\000b c'");
+        let mut command = String::from("printf");
+        error.append_arg(&mut command, "\0");
+        assert_eq!(&command, r"printf $'\000'");
+        assert_eq!(Quoter::new().quote(input), quotemeta(input));
+    }
+
+    #[test]
+    fn test_preserve_dot_prefix() {
+        let quoter = Quoter::new();
+        assert_eq!(&quoter.quote("./weird name"), "./'weird name'");
+        assert_eq!(&quoter.quote("../weird name"), "../'weird name'");
+        assert_eq!(&quoter.quote("./../../it's"), r"./../../$'it\'s'");
+        assert_eq!(&quoter.quote("./"), "./");
+        assert_eq!(&quoter.quote("."), ".");
+        assert_eq!(&quoter.quote(".."), "..");
+        assert_eq!(&quoter.quote("./plain"), "./plain");
+        // Only whole `.` and `..` components count.
+        assert_eq!(&quoter.quote(".hidden file"), "'.hidden file'");
+        assert_eq!(&quoter.quote(".../x y"), "'.../x y'");
+        assert_eq!(&quoter.quote("a/./b c"), "'a/./b c'");
+        let isolated = Quoter::new().style(QuoteStyle::IsolateDangerous);
+        assert_eq!(&isolated.quote("./a b\n"), r"./'a b'$'\012'");
+
+        let whole = Quoter::new().preserve_dot_prefix(false);
+        assert_eq!(&whole.quote("./weird name"), "'./weird name'");
+        assert_eq!(&whole.quote("../\n"), r"$'../\012'");
+
+        let inputs: &[&[u8]] = &[b"./a b", b"../\n", b"./../x'y", b"./"];
+        for &s in inputs {
+            assert_eq!(shell_words(&quoter.quote(OsStr::from_bytes(s))), [s]);
+        }
+    }
+
+    #[test]
+    fn test_literal_utf8() {
+        let quoter = Quoter::new().literal_utf8(is_printable);
+        assert_eq!(&quoter.quote("test"), "test");
+        assert_eq!(&quoter.quote("\u{a3}5"), "'\u{a3}5'");
+        assert_eq!(&quoter.quote("\u{1f980} crab"), "'\u{1f980} crab'");
+        assert_eq!(&quoter.quote("\u{e9}'\n"), "$'\u{e9}\\'\\012'");
+        // C1 controls and invalid UTF-8 are still escaped.
+        assert_eq!(&quoter.quote("\u{85}"), r"$'\302\205'");
+        assert_eq!(&quoter.quote(OsStr::from_bytes(b"\xc3\xa9\xff")), "$'\u{e9}\\377'");
+        let isolated = quoter.style(QuoteStyle::IsolateDangerous);
+        assert_eq!(&isolated.quote("na\u{ef}ve\n\u{e9}"), "'na\u{ef}ve'$'\\012''\u{e9}'");
+
+        // A custom predicate which treats an otherwise-printable character as unprintable.
+        let no_e_acute = Quoter::new().literal_utf8(|c| c != '\u{e9}' && is_printable(c));
+        assert_eq!(&no_e_acute.quote("\u{e9}\u{fc}"), "$'\\303\\251\u{fc}'");
+        assert_eq!(&no_e_acute.quote("\u{fc}"), "'\u{fc}'");
+        // ASCII is never affected by the predicate.
+        assert_eq!(&Quoter::new().literal_utf8(|_| false).quote("a b"), "'a b'");
+    }
+
+    #[cfg(feature = "unicode-normalization")]
+    #[test]
+    fn test_normalize() {
+        use crate::Normalization;
+
+        let (composed, decomposed) = ("caf\u{e9} \u{c5}", "cafe\u{301} A\u{30a}");
+        let nfc = Quoter::new().normalize(Normalization::Nfc);
+        assert_eq!(nfc.quote(composed), nfc.quote(decomposed));
+        assert_eq!(nfc.quote(decomposed), quotemeta(composed));
+        let nfd = Quoter::new().normalize(Normalization::Nfd);
+        assert_eq!(nfd.quote(composed), quotemeta(decomposed));
+        // Without normalization, the two differ.
+        assert_ne!(quotemeta(composed), quotemeta(decomposed));
+        // Invalid UTF-8 is left alone, even if it is next to text which is normalized.
+        let mixed = OsStr::from_bytes(b"e\xcc\x81\xff");
+        assert_eq!(&nfc.quote(mixed), r"$'\303\251\377'");
+        assert_eq!(&nfc.quote(OsStr::from_bytes(b"\xe9")), r"$'\351'");
+    }
+
+    /// The per-byte `quotemeta` which `write_verbatim` replaced, as it was before [`Quoter`]
+    /// existed, kept as an independent reference for the fast path.
+    fn baseline_quotemeta(s: &[u8]) -> String {
+        let (mut single_quoted, mut c_quoted) = (false, false);
+        let mut out = String::new();
+        for &c in s {
+            match c {
+                b'+' | b',' | b'-' | b'.' | b'/' | b'0' ..= b'9' | b':' | b'=' | b'@' => {
+                    out.push(char::from(c));
+                }
+                b'A' ..= b'Z' | b'_' | b'a' ..= b'z' => out.push(char::from(c)),
+                0 ..= 31 | 127 ..= 255 => {
+                    c_quoted = true;
+                    out.push('\\');
+                    out.extend([c >> 6, (c >> 3) & 7, c & 7].map(|digit| char::from(b'0' + digit)));
+                }
+                b'\'' | b'\\' => {
+                    c_quoted = true;
+                    out.push('\\');
+                    out.push(char::from(c));
+                }
+                _ => {
+                    single_quoted = true;
+                    out.push(char::from(c));
+                }
+            }
+        }
+        match (c_quoted, single_quoted) {
+            (true, _) => format!("$'{out}'"),
+            (false, true) => format!("'{out}'"),
+            (false, false) => out,
+        }
+    }
+
+    #[test]
+    fn test_verbatim_fast_path() {
+        let names = ["Documents and Settings", "My Music", "a b", " ", "(1) [2] {3} <4> ~5 !6"];
+        for name in &names {
+            assert_eq!(quotemeta(name), format!("'{name}'"));
+        }
+        for name in &["test", "/usr/bin/env", "a,b.c:d=e@f+g"] {
+            assert_eq!(&quotemeta(name), name);
+        }
+        let corpus: [&[u8]; 18] = [
+            b"",
+            b"test",
+            b"x",
+            b"-n",
+            b"~user",
+            b"a b",
+            b"(1) [2] {3} <4> ~5 !6",
+            b"./a b",
+            b"../../x/y z",
+            b"./",
+            b"it's",
+            b"tab\there",
+            "caf\u{e9}".as_bytes(),
+            "\u{65e5}\u{672c} \u{8a9e}".as_bytes(),
+            "zero\u{200b}width".as_bytes(),
+            b"caf\xe9",
+            b"\xff",
+            b"./\xff",
+        ];
+        // Without a dot prefix to keep outside the quotes, the output is the baseline's.
+        let whole = Quoter::new().preserve_dot_prefix(false);
+        for s in corpus {
+            assert_eq!(whole.quote_bytes(s), baseline_quotemeta(s), "{s:?}");
+        }
+        // Whatever the options, the output is a single word which the shell reads back as the
+        // input, and a forced tier is never left bare.
+        let quoters = [
+            (Quoter::new(), false),
+            (Quoter::new().min_tier(Tier::Single), true),
+            (Quoter::new().min_tier(Tier::CQuoted), true),
+            (Quoter::new().quote_single_char(true), false),
+            (Quoter::new().first_byte_policy(FirstBytePolicy::Conservative), false),
+            (Quoter::new().style(QuoteStyle::IsolateDangerous), false),
+            (Quoter::new().literal_utf8(is_printable), false),
+            (Quoter::new().literal_utf8(is_printable).min_tier(Tier::Single), true),
+        ];
+        for (i, (quoter, forced)) in quoters.iter().enumerate() {
+            for s in corpus {
+                let quoted = quoter.quote_bytes(s);
+                if quoted.is_empty() {
+                    assert_eq!(s, b"", "quoter {i}");
+                } else {
+                    assert_eq!(shell_words(&quoted), [s], "{s:?} quoter {i}");
+                }
+                if *forced {
+                    assert_eq!(quoted.chars().last(), Some('\''), "{s:?} quoter {i}");
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_from_spec() {
+        assert_eq!(Quoter::from_spec("").expect("valid spec"), Quoter::new());
+        assert_eq!(Quoter::from_spec(" ").expect("valid spec"), Quoter::new());
+        let quoter = Quoter::from_spec(
+            "isolate-dangerous, ctrl-letter,conservative,nul-replace=_,always-single,\
+             escape-limit=3,quote-single-char,no-dot-prefix,literal-utf8,ctrl-letter",
+        )
+        .expect("valid spec");
+        let expected = Quoter::new()
+            .style(QuoteStyle::IsolateDangerous)
+            .escape_format(EscapeFormat::CtrlLetter)
+            .first_byte_policy(FirstBytePolicy::Conservative)
+            .nul_policy(NulPolicy::Replace(b'_'))
+            .min_tier(Tier::Single)
+            .escape_limit(3)
+            .quote_single_char(true)
+            .preserve_dot_prefix(false)
+            .literal_utf8(is_printable);
+        assert_eq!(quoter, expected);
+        assert_eq!(Quoter::from_spec("nul-strip").expect("valid spec").quote("a\0b"), "ab");
+        assert_eq!(Quoter::from_spec("always-c-quoted").expect("valid spec").quote("a"), "$'a'");
+    }
+
+    #[test]
+    fn test_from_spec_errors() {
+        for token in ["posix", "hex", "Octal", "isolate", "whole=1", "escape-limit", "nfc=x", ""] {
+            let err = Quoter::from_spec(&format!("whole,{token}")).expect_err("invalid spec");
+            assert_eq!(err, SpecError::UnknownToken(token.to_owned()));
+        }
+        for token in ["escape-limit=-1", "escape-limit=x", "nul-replace=", "nul-replace=ab"] {
+            let err = Quoter::from_spec(token).expect_err("invalid spec");
+            assert_eq!(err, SpecError::InvalidValue(token.to_owned()));
+        }
+        let conflicts = [
+            ("whole", "isolate-dangerous"),
+            ("command", "argument"),
+            ("nul-strip", "nul-replace=_"),
+            ("nul-replace=_", "nul-replace=-"),
+            ("bare", "always-single"),
+            ("escape-limit=1", "escape-limit=2"),
+            ("dot-prefix", "no-dot-prefix"),
+        ];
+        for (first, second) in conflicts {
+            let err =
+                Quoter::from_spec(&format!("{first},octal,{second}")).expect_err("invalid spec");
+            assert_eq!(err, SpecError::Conflict(first.to_owned(), second.to_owned()));
+        }
+        let messages = [
+            (SpecError::UnknownToken(String::from("hex")), r#"unknown quoting option "hex""#),
+            (
+                SpecError::InvalidValue(String::from("escape-limit=x")),
+                r#"invalid value in quoting option "escape-limit=x""#,
+            ),
+            (
+                SpecError::Conflict(String::from("bare"), String::from("always-single")),
+                r#"quoting options "bare" and "always-single" conflict"#,
+            ),
+        ];
+        for (err, message) in messages {
+            assert_eq!(err.to_string(), message);
+        }
+    }
+
+    #[cfg(feature = "unicode-normalization")]
+    #[test]
+    fn test_from_spec_normalize() {
+        use crate::Normalization;
+
+        let nfd = Quoter::from_spec("nfd").expect("valid spec");
+        assert_eq!(nfd, Quoter::new().normalize(Normalization::Nfd));
+        let err = Quoter::from_spec("nfc,nfd").expect_err("invalid spec");
+        assert_eq!(err, SpecError::Conflict(String::from("nfc"), String::from("nfd")));
+    }
+}
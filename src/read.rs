@@ -0,0 +1,109 @@
+//! Lines for the shell's `read` builtin to read back.
+
+#[cfg(unix)] use std::os::unix::ffi::OsStrExt;
+use std::{error::Error, fmt, path::Path};
+
+/// The error returned by [`quotemeta_read_line`] when a value cannot be read back as a line.
+///
+/// Each variant holds the byte offset in the input at which the problem was found.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum NotReadable {
+    /// A newline, which would end the line early.
+    Newline(usize),
+    /// A NUL byte, which bash's `read` silently drops.
+    Nul(usize),
+    /// A byte which is not part of valid UTF-8.
+    InvalidUtf8(usize),
+}
+
+impl fmt::Display for NotReadable {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            Self::Newline(offset) => {
+                write!(f, "newline at offset {offset} cannot be read as part of a line")
+            }
+            Self::Nul(offset) => write!(f, "NUL at offset {offset} cannot be read by `read`"),
+            Self::InvalidUtf8(offset) => write!(f, "invalid UTF-8 at offset {offset}"),
+        }
+    }
+}
+
+impl Error for NotReadable {}
+
+/// Returns a line which `IFS= read -r var` reads back as the given [`Path`], for example to feed
+/// values to a script on its standard input.
+///
+/// `read` stops at the first newline. With `-r`, backslashes are taken literally rather than
+/// escaping the next character or continuing the line, and with `IFS` empty, leading and trailing
+/// blanks are kept rather than stripped. No quoting is therefore needed at all, or possible: the
+/// line is the value itself, followed by a newline. In particular, the value must not be quoted
+/// by [`quotemeta`](crate::quotemeta), since `read` would keep the quotes.
+///
+/// A value containing a newline cannot be represented, and nor can a NUL byte, which bash's `read`
+/// silently drops. A `String` cannot hold bytes which are not valid UTF-8, and replacing them
+/// would read back as a different value, so those are refused too.
+///
+/// # Errors
+///
+/// Returns [`NotReadable`] for the first newline, NUL or invalid UTF-8 in the input.
+///
+/// ```
+/// use quotemeta::{quotemeta_read_line, NotReadable};
+///
+/// assert_eq!(quotemeta_read_line(r"  C:\new dir ").unwrap(), "  C:\\new dir \n");
+/// assert_eq!(quotemeta_read_line("two\nlines"), Err(NotReadable::Newline(3)));
+/// ```
+pub fn quotemeta_read_line(s: impl AsRef<Path>) -> Result<String, NotReadable> {
+    let bytes = s.as_ref().as_os_str().as_bytes();
+    let text = bytes.utf8_chunks().next().map_or("", |chunk| chunk.valid());
+    for (offset, c) in text.bytes().enumerate() {
+        match c {
+            b'\n' => return Err(NotReadable::Newline(offset)),
+            b'\0' => return Err(NotReadable::Nul(offset)),
+            _ => {}
+        }
+    }
+    if text.len() < bytes.len() {
+        return Err(NotReadable::InvalidUtf8(text.len()));
+    }
+    let mut line = text.to_owned();
+    line.push('\n');
+    Ok(line)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{quotemeta_read_line, NotReadable};
+    #[cfg(unix)] use std::os::unix::ffi::OsStrExt;
+    use std::ffi::OsStr;
+
+    #[test]
+    fn test_quotemeta_read_line() {
+        assert_eq!(quotemeta_read_line("").expect("single line"), "\n");
+        assert_eq!(
+            quotemeta_read_line("  spaced  out  ").expect("single line"),
+            "  spaced  out  \n"
+        );
+        assert_eq!(
+            quotemeta_read_line("tab\tand 'quotes'").expect("single line"),
+            "tab\tand 'quotes'\n"
+        );
+        // Backslashes are literal with `read -r`, even at the end of the line.
+        assert_eq!(quotemeta_read_line(r"back\slash\").expect("single line"), "back\\slash\\\n");
+        assert_eq!(quotemeta_read_line("caf\u{e9}").expect("single line"), "caf\u{e9}\n");
+        assert_eq!(quotemeta_read_line("one\ntwo\n"), Err(NotReadable::Newline(3)));
+        assert_eq!(quotemeta_read_line("nul\0"), Err(NotReadable::Nul(3)));
+        let invalid = |s: &[u8]| quotemeta_read_line(OsStr::from_bytes(s));
+        assert_eq!(invalid(b"\xff"), Err(NotReadable::InvalidUtf8(0)));
+        assert_eq!(invalid(b"caf\xe9"), Err(NotReadable::InvalidUtf8(3)));
+        // The first problem is reported, whatever its kind.
+        assert_eq!(invalid(b"a\0\n\xff"), Err(NotReadable::Nul(1)));
+        assert_eq!(invalid(b"a\n\0\xff"), Err(NotReadable::Newline(1)));
+        assert_eq!(invalid(b"a\xff\n\0"), Err(NotReadable::InvalidUtf8(1)));
+        assert_eq!(
+            NotReadable::Newline(3).to_string(),
+            "newline at offset 3 cannot be read as part of a line"
+        );
+    }
+}
@@ -0,0 +1,115 @@
+//! Framing quoted values for transport over a byte stream.
+
+use crate::quotemeta;
+use std::{
+    convert::TryFrom,
+    io::{self, Read, Write},
+    path::Path,
+};
+
+/// Shell-quotes the given [`Path`] and writes it as a length-prefixed record: the length of the
+/// quoted form in bytes, as a 4-byte big-endian integer, followed by the quoted form itself.
+///
+/// The framing makes the boundaries between values unambiguous without relying on a delimiter.
+/// Use [`read_record`] to read the records back.
+///
+/// # Errors
+///
+/// Returns any error from writing to `w`, or one of kind [`io::ErrorKind::InvalidInput`] if the
+/// quoted form is too long for its length to fit in 4 bytes.
+///
+/// ```
+/// use quotemeta::quotemeta_record;
+///
+/// let mut out = Vec::new();
+/// quotemeta_record("a b", &mut out).unwrap();
+/// assert_eq!(out, b"\0\0\0\x05'a b'");
+/// ```
+#[cfg_attr(
+    feature = "clippy-insane",
+    expect(clippy::big_endian_bytes, reason = "network byte order")
+)]
+pub fn quotemeta_record(s: impl AsRef<Path>, w: &mut impl Write) -> io::Result<()> {
+    let quoted = quotemeta(s);
+    let len = u32::try_from(quoted.len())
+        .map_err(|err| io::Error::new(io::ErrorKind::InvalidInput, err))?;
+    w.write_all(&len.to_be_bytes())?;
+    w.write_all(quoted.as_bytes())
+}
+
+/// Reads a record written by [`quotemeta_record`], returning the quoted form, or `None` if the
+/// stream ends cleanly before the record starts.
+///
+/// The result is still quoted; pass it to [`unquotemeta`](crate::unquotemeta) to recover the
+/// original value.
+///
+/// # Errors
+///
+/// Returns any error from reading `r`, one of kind [`io::ErrorKind::UnexpectedEof`] if the stream
+/// ends part-way through a record, or one of kind [`io::ErrorKind::InvalidData`] if the record is
+/// not valid UTF-8.
+///
+/// ```
+/// use quotemeta::{quotemeta_record, read_record};
+///
+/// let mut stream = Vec::new();
+/// quotemeta_record("it's", &mut stream).unwrap();
+/// let mut input = stream.as_slice();
+/// assert_eq!(read_record(&mut input).unwrap().as_deref(), Some(r"$'it\'s'"));
+/// assert_eq!(read_record(&mut input).unwrap(), None);
+/// ```
+#[cfg_attr(
+    feature = "clippy-insane",
+    expect(clippy::big_endian_bytes, reason = "network byte order")
+)]
+pub fn read_record(r: &mut impl Read) -> io::Result<Option<String>> {
+    let mut len = [0; 4];
+    let (first, rest) = len.split_at_mut(1);
+    loop {
+        match r.read(first) {
+            Ok(0) => return Ok(None),
+            Ok(_) => break,
+            Err(err) if err.kind() == io::ErrorKind::Interrupted => {}
+            Err(err) => return Err(err),
+        }
+    }
+    r.read_exact(rest)?;
+    let len = usize::try_from(u32::from_be_bytes(len))
+        .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+    let mut quoted = vec![0; len];
+    r.read_exact(&mut quoted)?;
+    String::from_utf8(quoted)
+        .map(Some)
+        .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{quotemeta, quotemeta_record, read_record, unquotemeta};
+    #[cfg(unix)] use std::os::unix::ffi::OsStrExt;
+    use std::{ffi::OsStr, io};
+
+    #[test]
+    fn test_record_round_trip() {
+        let values: &[&[u8]] = &[b"plain", b"", b"a b", b"it's", b"\n\xff\0", b"./x y"];
+        let mut stream = Vec::new();
+        for &value in values {
+            quotemeta_record(OsStr::from_bytes(value), &mut stream).expect("writes");
+        }
+        let mut input = stream.as_slice();
+        for &value in values {
+            let quoted = read_record(&mut input).expect("reads").expect("a record");
+            assert_eq!(quoted, quotemeta(OsStr::from_bytes(value)));
+            assert_eq!(unquotemeta(&quoted).expect("parses"), OsStr::from_bytes(value));
+        }
+        assert_eq!(read_record(&mut input).expect("reads"), None);
+    }
+
+    #[test]
+    fn test_read_record_errors() {
+        let truncated = |mut input: &[u8]| read_record(&mut input).expect_err("truncated").kind();
+        assert_eq!(truncated(b"\0\0"), io::ErrorKind::UnexpectedEof);
+        assert_eq!(truncated(b"\0\0\0\x05'a b"), io::ErrorKind::UnexpectedEof);
+        assert_eq!(truncated(b"\0\0\0\x01\xff"), io::ErrorKind::InvalidData);
+    }
+}
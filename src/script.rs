@@ -0,0 +1,725 @@
+//! Generating fragments of shell script, such as variable assignments.
+
+use crate::{
+    command::{join_bash, join_quoted},
+    quotemeta, Quoter, Tier,
+};
+#[cfg(unix)] use std::os::unix::ffi::OsStrExt;
+use std::{error::Error, fmt, path::Path, str};
+
+/// A shell which generated scripts may be written for.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum Shell {
+    /// A POSIX `/bin/sh`, which lacks `$'...'` and so only has single quotes to work with.
+    Sh,
+    /// GNU bash, which gets the same quoting as [`quotemeta`].
+    Bash,
+}
+
+impl Shell {
+    /// Shell-quotes the given [`Path`] for this shell.
+    ///
+    /// [`Shell::Bash`] is the same as [`quotemeta`], and never fails. [`Shell::Sh`] single-quotes
+    /// anything which is not bare, and writes single quotes as `'\''`. Control characters and
+    /// non-ASCII text are included as-is, since a single-quoted string can contain any byte except
+    /// `'` itself, but the result may be difficult to read or to copy and paste.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`NotPortable::InvalidUtf8`] for [`Shell::Sh`] if the input is not valid UTF-8,
+    /// since a `String` cannot hold it and the only other way to write it is bash's `$'...'`.
+    ///
+    /// ```
+    /// use quotemeta::{NotPortable, Shell};
+    /// # #[cfg(unix)] use std::os::unix::ffi::OsStrExt;
+    /// use std::ffi::OsStr;
+    ///
+    /// assert_eq!(Shell::Sh.quote("it's").as_deref(), Ok(r"'it'\''s'"));
+    /// assert_eq!(Shell::Bash.quote("it's").as_deref(), Ok(r"$'it\'s'"));
+    /// assert_eq!(Shell::Sh.quote(OsStr::from_bytes(b"\xff")), Err(NotPortable::InvalidUtf8(0)));
+    /// ```
+    pub fn quote(self, s: impl AsRef<Path>) -> Result<String, NotPortable> {
+        let bytes = s.as_ref().as_os_str().as_bytes();
+        if self == Self::Bash {
+            return Ok(quotemeta(s));
+        }
+        let invalid = |err: str::Utf8Error| NotPortable::InvalidUtf8(err.valid_up_to());
+        let text = str::from_utf8(bytes).map_err(invalid)?;
+        if Tier::of(bytes) == Tier::Bare {
+            Ok(text.to_owned())
+        } else {
+            Ok(format!("'{}'", text.replace('\'', r"'\''")))
+        }
+    }
+
+    /// Returns the `#!` line which runs a script with this shell.
+    const fn shebang(self) -> &'static str {
+        match self {
+            Self::Sh => "#!/bin/sh",
+            Self::Bash => "#!/bin/bash",
+        }
+    }
+
+    /// Returns the `set` command which makes a script stop at the first sign of trouble.
+    const fn strict_mode(self) -> &'static str {
+        match self {
+            Self::Sh => "set -eu",
+            Self::Bash => "set -euo pipefail",
+        }
+    }
+}
+
+/// The shell required to run the output of [`quotemeta_portable_first`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[non_exhaustive]
+pub enum ShellRequirement {
+    /// Any POSIX shell will do.
+    Posix,
+    /// bash (or another shell with `$'...'`) is required.
+    Bash,
+}
+
+impl ShellRequirement {
+    /// Returns the least capable [`Shell`] which meets the requirement.
+    #[must_use]
+    pub const fn shell(self) -> Shell {
+        match self {
+            Self::Posix => Shell::Sh,
+            Self::Bash => Shell::Bash,
+        }
+    }
+}
+
+/// Shell-quotes the given [`Path`] portably if possible, and otherwise for bash.
+///
+/// Printable ASCII is quoted as for [`Shell::Sh`], which any POSIX shell understands. Control
+/// characters and non-ASCII bytes could technically be single-quoted too, but are hard to read and
+/// easily mangled in transit, so they are written as for [`Shell::Bash`] instead. The second part
+/// of the result says which was used, so that the caller can write a suitable `#!` line.
+///
+/// ```
+/// use quotemeta::{quotemeta_portable_first, ShellRequirement};
+///
+/// let (quoted, requirement) = quotemeta_portable_first("it's");
+/// assert_eq!((quoted.as_str(), requirement), (r"'it'\''s'", ShellRequirement::Posix));
+/// let (quoted, requirement) = quotemeta_portable_first("\t");
+/// assert_eq!((quoted.as_str(), requirement), (r"$'\011'", ShellRequirement::Bash));
+/// ```
+pub fn quotemeta_portable_first(s: impl AsRef<Path>) -> (String, ShellRequirement) {
+    let bytes = s.as_ref().as_os_str().as_bytes();
+    if !bytes.iter().any(|&c| matches!(c, 0 ..= 31 | 127 ..= 255)) {
+        if let Ok(quoted) = Shell::Sh.quote(&s) {
+            return (quoted, ShellRequirement::Posix);
+        }
+    }
+    (quotemeta(s), ShellRequirement::Bash)
+}
+
+/// The error returned by [`quotemeta_ash`] and [`Shell::quote`] when a string has no reliable
+/// portable representation.
+///
+/// Each variant holds the byte offset in the input at which the problem was found.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum NotPortable {
+    /// A control character, including tab, newline and the C1 controls U+0080 to U+009F.
+    Control(usize),
+    /// A byte which is not part of valid UTF-8.
+    InvalidUtf8(usize),
+    /// A bidirectional formatting character, which can make the text display in a different
+    /// order from the one in which the shell reads it.
+    Bidi(usize),
+}
+
+impl fmt::Display for NotPortable {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            Self::Control(offset) => write!(f, "control character at offset {offset}"),
+            Self::InvalidUtf8(offset) => write!(f, "invalid UTF-8 at offset {offset}"),
+            Self::Bidi(offset) => {
+                write!(f, "bidirectional formatting character at offset {offset}")
+            }
+        }
+    }
+}
+
+impl Error for NotPortable {}
+
+/// Shell-quotes the given [`Path`] for the most minimal of `/bin/sh` implementations, such as
+/// the `ash` of `busybox` on an embedded system.
+///
+/// This uses nothing but single quotes, with single quotes themselves written as `'\''`. Text
+/// which [`quotemeta`] leaves bare is left bare here too, and an empty string is written as `''`.
+/// Unlike [`Shell::Sh`], this refuses input which it cannot represent reliably: control
+/// characters (bytes 0 to 31 and 127, so including tab, newline and NUL, and the C1 controls
+/// U+0080 to U+009F) could technically be single-quoted, but are easily mangled in transit and
+/// are a common source of bugs in small shells, bidirectional formatting characters such as
+/// U+202E can make a command display as something other than what it runs, and bytes which are
+/// not valid UTF-8 cannot be held in a `String` at all. Any other UTF-8 is single-quoted as-is.
+///
+/// # Errors
+///
+/// Returns [`NotPortable`] for whichever comes first in the input of a control character, a
+/// bidirectional formatting character and invalid UTF-8.
+///
+/// ```
+/// use quotemeta::{quotemeta_ash, NotPortable};
+///
+/// assert_eq!(quotemeta_ash("it's").unwrap(), r"'it'\''s'");
+/// assert_eq!(quotemeta_ash("two\nlines"), Err(NotPortable::Control(3)));
+/// assert_eq!(quotemeta_ash("a\u{202e}b"), Err(NotPortable::Bidi(1)));
+/// ```
+pub fn quotemeta_ash(s: impl AsRef<Path>) -> Result<String, NotPortable> {
+    let bytes = s.as_ref().as_os_str().as_bytes();
+    let text = bytes.utf8_chunks().next().map_or("", |chunk| chunk.valid());
+    for (offset, c) in text.char_indices() {
+        if c.is_control() {
+            return Err(NotPortable::Control(offset));
+        }
+        if is_bidi_format(c) {
+            return Err(NotPortable::Bidi(offset));
+        }
+    }
+    if text.len() < bytes.len() {
+        return Err(NotPortable::InvalidUtf8(text.len()));
+    }
+    match Tier::of(bytes) {
+        _ if text.is_empty() => Ok(String::from("''")),
+        Tier::Bare => Ok(text.to_owned()),
+        Tier::Single | Tier::CQuoted => Ok(format!("'{}'", text.replace('\'', r"'\''"))),
+    }
+}
+
+/// Returns whether the character is one of the explicit bidirectional formatting characters: the
+/// Arabic letter mark, the left-to-right and right-to-left marks, and the embeddings, overrides and
+/// isolates with their terminators.
+const fn is_bidi_format(c: char) -> bool {
+    matches!(
+        c,
+        '\u{61c}' | '\u{200e}' | '\u{200f}' | '\u{202a}' ..= '\u{202e}' | '\u{2066}' ..= '\u{2069}'
+    )
+}
+
+/// Shell-quotes the given [`Path`] so that bash and zsh both read it as the same word.
+///
+/// zsh supports the `$'...'` quoting of [`quotemeta`], with the same escapes, and expands the
+/// same characters which [`quotemeta`] quotes, with one exception: a word starting with an
+/// unquoted `=` is replaced with the path of the command it names, so `=ls` becomes `/bin/ls`.
+/// bash takes it literally. The combined rules are therefore:
+///
+/// - An empty string is written as `''`, since it would otherwise vanish in both shells.
+/// - A string starting with `=` is quoted, and single-quoted if [`quotemeta`] would have left it
+///   bare.
+/// - Anything else is quoted exactly as by [`quotemeta`]. In particular, `~` is always quoted,
+///   which both shells also expand at the start of a word, and zsh's additional glob characters
+///   `^` and `#` are never left bare.
+///
+/// ```
+/// use quotemeta::{quotemeta, quotemeta_bash_zsh};
+///
+/// assert_eq!(&quotemeta("=ls"), "=ls");
+/// assert_eq!(&quotemeta_bash_zsh("=ls"), "'=ls'");
+/// assert_eq!(&quotemeta_bash_zsh("a=b c"), "'a=b c'");
+/// ```
+pub fn quotemeta_bash_zsh(s: impl AsRef<Path>) -> String {
+    match s.as_ref().as_os_str().as_bytes().first() {
+        None => String::from("''"),
+        Some(b'=') => Quoter::new().min_tier(Tier::Single).quote(s),
+        Some(_) => quotemeta(s),
+    }
+}
+
+/// The error returned when a string is not a valid shell variable name.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(
+    feature = "clippy-insane",
+    expect(clippy::exhaustive_structs, reason = "the name is all there is to report")
+)]
+pub struct InvalidIdentifier(pub String);
+
+impl fmt::Display for InvalidIdentifier {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:?} is not a valid shell variable name", self.0)
+    }
+}
+
+impl Error for InvalidIdentifier {}
+
+/// Returns whether `name` is a valid shell variable name: an ASCII letter or underscore, followed
+/// by any number of ASCII letters, digits and underscores.
+pub(crate) fn is_identifier(name: &[u8]) -> bool {
+    match name.split_first() {
+        Some((b'A' ..= b'Z' | b'a' ..= b'z' | b'_', rest)) => {
+            rest.iter().all(|&c| c.is_ascii_alphanumeric() || c == b'_')
+        }
+        _ => false,
+    }
+}
+
+/// Checks that `name` is a valid shell variable name, as for [`is_identifier`].
+pub(crate) fn check_identifier(name: &str) -> Result<(), InvalidIdentifier> {
+    if is_identifier(name.as_bytes()) { Ok(()) } else { Err(InvalidIdentifier(name.to_owned())) }
+}
+
+/// The error returned when a string is not a safe numeric literal for shell arithmetic.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(
+    feature = "clippy-insane",
+    expect(clippy::exhaustive_structs, reason = "the literal is all there is to report")
+)]
+pub struct NonNumeric(pub String);
+
+impl fmt::Display for NonNumeric {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:?} is not a safe numeric literal for shell arithmetic", self.0)
+    }
+}
+
+impl Error for NonNumeric {}
+
+/// Checks that the given [`Path`] is a numeric literal which may be used in shell arithmetic, such
+/// as within `$(( ... ))`, and returns it ready for use there.
+///
+/// There is no way to quote arbitrary data in arithmetic context: the shell recursively expands
+/// any variable names it finds, and array subscripts such as `a[$(cmd)]` run commands. This
+/// therefore only accepts a decimal integer, or a hexadecimal one starting with `0x` or `0X`,
+/// optionally preceded by a `-` or `+`. Signed values are returned in parentheses, so that
+/// `$((5-(-1)))` is not mistaken for a decrement. Note that a decimal number with a leading `0` is
+/// read as octal, and `08` is an error.
+///
+/// # Errors
+///
+/// Returns [`NonNumeric`] for anything else, including an empty string, a variable name or an
+/// expression such as `1+1`.
+///
+/// ```
+/// use quotemeta::quotemeta_arith;
+///
+/// assert_eq!(quotemeta_arith("42").unwrap(), "42");
+/// assert_eq!(format!("$((x - {}))", quotemeta_arith("-1").unwrap()), "$((x - (-1)))");
+/// assert!(quotemeta_arith("a[$(reboot)]").is_err());
+/// ```
+pub fn quotemeta_arith(s: impl AsRef<Path>) -> Result<String, NonNumeric> {
+    let bytes = s.as_ref().as_os_str().as_bytes();
+    let (signed, magnitude) = match bytes.split_first() {
+        Some((b'-' | b'+', rest)) => (true, rest),
+        _ => (false, bytes),
+    };
+    let valid = match magnitude {
+        [b'0', b'x' | b'X', digits @ ..] => {
+            !digits.is_empty() && digits.iter().all(u8::is_ascii_hexdigit)
+        }
+        digits => !digits.is_empty() && digits.iter().all(u8::is_ascii_digit),
+    };
+    let text = String::from_utf8_lossy(bytes).into_owned();
+    match (valid, signed) {
+        (true, true) => Ok(format!("({text})")),
+        (true, false) => Ok(text),
+        (false, _) => Err(NonNumeric(text)),
+    }
+}
+
+/// Generates a shell variable assignment which sets `name` to the given [`Path`].
+///
+/// The result is `name=value`, or `declare -- name=value` if `declare` is set, with the value
+/// quoted by [`quotemeta`]. The latter is bash-specific, but makes the variable local when sourced
+/// from within a function.
+///
+/// # Errors
+///
+/// Returns [`InvalidIdentifier`] if `name` is not a valid variable name.
+///
+/// ```
+/// use quotemeta::quotemeta_assign;
+///
+/// assert_eq!(quotemeta_assign("DIR", "/tmp/my dir", false).unwrap(), "DIR='/tmp/my dir'");
+/// assert_eq!(quotemeta_assign("N", "\n", true).unwrap(), r"declare -- N=$'\012'");
+/// assert!(quotemeta_assign("2BAD", "", false).is_err());
+/// ```
+pub fn quotemeta_assign(
+    name: &str, value: impl AsRef<Path>, declare: bool,
+) -> Result<String, InvalidIdentifier> {
+    check_identifier(name)?;
+    let declare = if declare { "declare -- " } else { "" };
+    Ok(format!("{declare}{name}={}", quotemeta(value)))
+}
+
+/// The error returned by [`quotemeta_dotenv`] when a line of a `.env` file cannot be written.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum DotenvError {
+    /// The key is not a valid variable name.
+    InvalidKey(InvalidIdentifier),
+    /// The value has a backslash followed by `n` or `r`, and also a single quote or line break, so
+    /// that it cannot be quoted either way. This holds the byte offset of the backslash.
+    Unrepresentable(usize),
+}
+
+impl fmt::Display for DotenvError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::InvalidKey(err) => err.fmt(f),
+            Self::Unrepresentable(offset) => {
+                write!(f, "backslash at offset {offset} cannot be written to a .env file")
+            }
+        }
+    }
+}
+
+impl Error for DotenvError {}
+
+impl From<InvalidIdentifier> for DotenvError {
+    fn from(err: InvalidIdentifier) -> Self { Self::InvalidKey(err) }
+}
+
+/// Generates a line of a `.env` file which sets the variable `key` to the given [`Path`].
+///
+/// There is no standard for `.env` files, and parsers disagree on the details. This targets the
+/// dialect of Ruby's `dotenv`, which most others follow for simple values. A value made up only
+/// of characters which [`quotemeta`] leaves bare is written as-is, since it cannot be mistaken for
+/// anything else. Any other value is enclosed in double quotes, within which a newline or carriage
+/// return is written as `\n` or `\r`, and `\`, `"` and `$` are backslash-escaped. The quotes
+/// keep a `#` from starting a comment, and escaping `$` stops `dotenv` from expanding variables
+/// and commands. Be aware that some parsers, such as `python-dotenv`, do not understand `\$`.
+/// Since a `.env` file is text, any bytes which are not valid UTF-8 are replaced with U+FFFD.
+///
+/// `dotenv` turns every `\n` and `\r` within double quotes into a line break before it removes
+/// the other escapes, so a value such as `C:\new` cannot be double-quoted: the escaped backslash
+/// would itself start a `\n`. Such a value is single-quoted instead, which `dotenv` takes
+/// literally, unless it also contains a single quote or a line break.
+///
+/// # Errors
+///
+/// Returns [`DotenvError::InvalidKey`] if `key` is not a valid variable name, or
+/// [`DotenvError::Unrepresentable`] if the value can be neither double- nor single-quoted.
+///
+/// ```
+/// use quotemeta::{quotemeta_dotenv, DotenvError};
+///
+/// assert_eq!(quotemeta_dotenv("PORT", "8080").unwrap(), "PORT=8080");
+/// assert_eq!(quotemeta_dotenv("GREETING", "hi #1\n").unwrap(), r#"GREETING="hi #1\n""#);
+/// assert_eq!(quotemeta_dotenv("DIR", r"C:\new dir").unwrap(), r"DIR='C:\new dir'");
+/// assert_eq!(quotemeta_dotenv("DIR", r"C:\new's"), Err(DotenvError::Unrepresentable(2)));
+/// ```
+pub fn quotemeta_dotenv(key: &str, value: impl AsRef<Path>) -> Result<String, DotenvError> {
+    check_identifier(key)?;
+    let bytes = value.as_ref().as_os_str().as_bytes();
+    if Tier::of(bytes) == Tier::Bare {
+        return Ok(format!("{key}={}", String::from_utf8_lossy(bytes)));
+    }
+    if let Some(offset) = bytes.windows(2).position(|pair| matches!(pair, b"\\n" | b"\\r")) {
+        if bytes.iter().any(|&c| matches!(c, b'\'' | b'\n' | b'\r')) {
+            return Err(DotenvError::Unrepresentable(offset));
+        }
+        return Ok(format!("{key}='{}'", String::from_utf8_lossy(bytes)));
+    }
+    let mut out = format!("{key}=\"");
+    for c in String::from_utf8_lossy(bytes).chars() {
+        match c {
+            '\n' => out.push_str(r"\n"),
+            '\r' => out.push_str(r"\r"),
+            '\\' | '"' | '$' => {
+                out.push('\\');
+                out.push(c);
+            }
+            _ => out.push(c),
+        }
+    }
+    out.push('"');
+    Ok(out)
+}
+
+/// Generates a bash array assignment which sets `name` to the given arguments.
+///
+/// The result is `name=( arg... )`, with each argument quoted by [`quotemeta`] and empty arguments
+/// written as `''` so that they are not lost. This keeps the arguments separate for a script to
+/// reuse, as in `"${name[@]}"`, which a flat string cannot do.
+///
+/// # Errors
+///
+/// Returns [`InvalidIdentifier`] if `name` is not a valid variable name.
+///
+/// ```
+/// use quotemeta::quotemeta_bash_array;
+///
+/// let array = quotemeta_bash_array("cmd", ["grep", "a b", "", "\n"]).unwrap();
+/// assert_eq!(&array, r"cmd=( grep 'a b' '' $'\012' )");
+/// ```
+pub fn quotemeta_bash_array<I, S>(name: &str, args: I) -> Result<String, InvalidIdentifier>
+where
+    I: IntoIterator<Item = S>,
+    S: AsRef<Path>,
+{
+    check_identifier(name)?;
+    let words = join_bash(args);
+    if words.is_empty() { Ok(format!("{name}=()")) } else { Ok(format!("{name}=( {words} )")) }
+}
+
+/// Generates a bash expansion which quotes the runtime value of the variable `name`.
+///
+/// The result is `"${name@Q}"`, which bash expands to the value of the variable, quoted so that
+/// it may be read back as shell input. This quotes nothing itself, but suits a generated script
+/// which needs to re-quote a value it only has at runtime, for example to log a command line.
+/// The `@Q` operator needs bash 4.4 or later, and is not available in other shells.
+///
+/// # Errors
+///
+/// Returns [`InvalidIdentifier`] if `name` is not a valid variable name.
+///
+/// ```
+/// use quotemeta::quotemeta_var_q;
+///
+/// assert_eq!(quotemeta_var_q("file").unwrap(), r#""${file@Q}""#);
+/// assert!(quotemeta_var_q("$(reboot)").is_err());
+/// ```
+pub fn quotemeta_var_q(name: &str) -> Result<String, InvalidIdentifier> {
+    check_identifier(name)?;
+    Ok(format!("\"${{{name}@Q}}\""))
+}
+
+/// Generates a small script which runs the given command, for example to attach to a bug report.
+///
+/// The script consists of a `#!` line for the `shell`, a `set` command which makes it stop on
+/// errors and unset variables (and failed pipelines, for bash), and then the command line with
+/// each argument quoted by [`Shell::quote`]. Empty arguments are written as `''` so that they are
+/// not lost.
+///
+/// # Errors
+///
+/// Returns [`NotPortable`] if [`Shell::quote`] cannot quote one of the arguments for the `shell`.
+///
+/// ```
+/// use quotemeta::{reproduction_script, Shell};
+///
+/// let script = reproduction_script(["grep", "-r", "hello world", "."], Shell::Bash).unwrap();
+/// assert_eq!(&script, "#!/bin/bash\nset -euo pipefail\ngrep -r 'hello world' .\n");
+/// ```
+pub fn reproduction_script<I, S>(args: I, shell: Shell) -> Result<String, NotPortable>
+where
+    I: IntoIterator<Item = S>,
+    S: AsRef<Path>,
+{
+    let command = join_quoted(args, shell)?;
+    Ok(format!("{}\n{}\n{command}\n", shell.shebang(), shell.strict_mode()))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        quotemeta, quotemeta_arith, quotemeta_ash, quotemeta_assign, quotemeta_bash_array,
+        quotemeta_bash_zsh, quotemeta_dotenv, quotemeta_portable_first, quotemeta_var_q,
+        reproduction_script, script::check_identifier, tests::shell_words, unquotemeta,
+        DotenvError, InvalidIdentifier, NonNumeric, NotPortable, Shell, ShellRequirement,
+    };
+    #[cfg(unix)] use std::os::unix::ffi::OsStrExt;
+    use std::ffi::OsStr;
+
+    #[test]
+    fn test_shell_quote() {
+        for s in [&b""[..], b"test", b"a b", b"it's", b"\n", "\u{e9}".as_bytes(), b"\xff"] {
+            let s = OsStr::from_bytes(s);
+            assert_eq!(Shell::Bash.quote(s), Ok(quotemeta(s)));
+        }
+        let sh = |s: &str| Shell::Sh.quote(s).expect("valid UTF-8");
+        assert_eq!(&sh(""), "");
+        assert_eq!(&sh("/bin/cat"), "/bin/cat");
+        assert_eq!(&sh("a b"), "'a b'");
+        assert_eq!(&sh("'"), r"''\'''");
+        assert_eq!(&sh(r"a\b"), r"'a\b'");
+        assert_eq!(&sh("line\nbreak"), "'line\nbreak'");
+        assert_eq!(&sh("\u{e9}"), "'\u{e9}'");
+        // There is no way to write invalid UTF-8 in a `String` without `$'...'`.
+        let invalid = |s: &[u8]| Shell::Sh.quote(OsStr::from_bytes(s));
+        assert_eq!(invalid(b"\xff"), Err(NotPortable::InvalidUtf8(0)));
+        assert_eq!(invalid(b"caf\xe9"), Err(NotPortable::InvalidUtf8(3)));
+    }
+
+    #[test]
+    fn test_quotemeta_portable_first() {
+        let posix = |s: &str| (String::from(s), ShellRequirement::Posix);
+        let bash = |s: &str| (String::from(s), ShellRequirement::Bash);
+        assert_eq!(quotemeta_portable_first(""), posix(""));
+        assert_eq!(quotemeta_portable_first("test"), posix("test"));
+        assert_eq!(quotemeta_portable_first("a space"), posix("'a space'"));
+        assert_eq!(quotemeta_portable_first(r"it's a\b"), posix(r"'it'\''s a\b'"));
+        assert_eq!(quotemeta_portable_first("bell\x07"), bash(r"$'bell\007'"));
+        assert_eq!(quotemeta_portable_first("\u{e9}"), bash(r"$'\303\251'"));
+        assert_eq!(quotemeta_portable_first(OsStr::from_bytes(b"\xff")), bash(r"$'\377'"));
+        assert_eq!(ShellRequirement::Posix.shell(), Shell::Sh);
+        assert_eq!(ShellRequirement::Bash.shell(), Shell::Bash);
+    }
+
+    #[test]
+    fn test_reproduction_script() {
+        let args = ["printf", "%s\n", "", "it's"];
+        assert_eq!(
+            reproduction_script(args, Shell::Bash).as_deref(),
+            Ok("#!/bin/bash\nset -euo pipefail\nprintf $'%s\\012' '' $'it\\'s'\n")
+        );
+        assert_eq!(
+            reproduction_script(args, Shell::Sh).as_deref(),
+            Ok("#!/bin/sh\nset -eu\nprintf '%s\n' '' 'it'\\''s'\n")
+        );
+        let script = reproduction_script([OsStr::new("cat"), OsStr::new("my file")], Shell::Sh)
+            .expect("valid UTF-8");
+        assert_eq!(script.lines().next(), Some("#!/bin/sh"));
+        assert_eq!(script.lines().last(), Some("cat 'my file'"));
+        let binary = [OsStr::new("cat"), OsStr::from_bytes(b"\xff")];
+        assert_eq!(reproduction_script(binary, Shell::Sh), Err(NotPortable::InvalidUtf8(0)));
+        let bash = reproduction_script(binary, Shell::Bash).expect("bash can quote anything");
+        assert_eq!(bash.lines().last(), Some(r"cat $'\377'"));
+    }
+
+    #[test]
+    fn test_quotemeta_arith() {
+        for s in &["0", "42", "0x1F", "0XfF", "007"] {
+            assert_eq!(quotemeta_arith(s).as_deref(), Ok(*s));
+        }
+        assert_eq!(quotemeta_arith("-42").as_deref(), Ok("(-42)"));
+        assert_eq!(quotemeta_arith("+0x10").as_deref(), Ok("(+0x10)"));
+        for s in &["", "-", "0x", "1+1", "foo", "1 ", " 1", "16#ff", "1.5", "a[$(id)]", "--1"] {
+            assert_eq!(quotemeta_arith(s), Err(NonNumeric(String::from(*s))));
+        }
+        assert_eq!(
+            quotemeta_arith("x").expect_err("invalid name").to_string(),
+            r#""x" is not a safe numeric literal for shell arithmetic"#
+        );
+    }
+
+    #[test]
+    fn test_check_identifier() {
+        for name in &["a", "_", "PATH", "_x1", "abc_DEF_123"] {
+            assert_eq!(check_identifier(name), Ok(()));
+        }
+        for name in &["", "1a", "a-b", "a b", "$a", "caf\u{e9}", "a="] {
+            assert_eq!(check_identifier(name), Err(InvalidIdentifier(String::from(*name))));
+        }
+    }
+
+    #[test]
+    fn test_quotemeta_assign() {
+        assert_eq!(quotemeta_assign("V", "plain", false).expect("valid name"), "V=plain");
+        assert_eq!(quotemeta_assign("V", "", false).expect("valid name"), "V=");
+        assert_eq!(quotemeta_assign("V", "a b", false).expect("valid name"), "V='a b'");
+        assert_eq!(quotemeta_assign("V", "a b", true).expect("valid name"), "declare -- V='a b'");
+        assert_eq!(quotemeta_assign("V", "a\tb", false).expect("valid name"), r"V=$'a\011b'");
+        assert_eq!(
+            quotemeta_assign("V", "a\tb", true).expect("valid name"),
+            r"declare -- V=$'a\011b'"
+        );
+        assert_eq!(
+            quotemeta_assign("a-b", "x", true).expect_err("invalid name").to_string(),
+            r#""a-b" is not a valid shell variable name"#
+        );
+    }
+
+    #[test]
+    fn test_quotemeta_ash() {
+        assert_eq!(quotemeta_ash("").expect("representable"), "''");
+        assert_eq!(quotemeta_ash("/usr/bin/env").expect("representable"), "/usr/bin/env");
+        assert_eq!(quotemeta_ash("hello world").expect("representable"), "'hello world'");
+        assert_eq!(quotemeta_ash("it's").expect("representable"), r"'it'\''s'");
+        assert_eq!(quotemeta_ash(r"$HOME\'").expect("representable"), r"'$HOME\'\'''");
+        assert_eq!(quotemeta_ash("caf\u{e9}").expect("representable"), "'caf\u{e9}'");
+        let quoted = quotemeta_ash(r"it's a \ test").expect("representable");
+        assert_eq!(unquotemeta(&quoted).expect("parses"), r"it's a \ test");
+
+        assert_eq!(quotemeta_ash("tab\there"), Err(NotPortable::Control(3)));
+        assert_eq!(quotemeta_ash("\0"), Err(NotPortable::Control(0)));
+        assert_eq!(quotemeta_ash("del\x7f"), Err(NotPortable::Control(3)));
+        assert_eq!(quotemeta_ash(OsStr::from_bytes(b"ab\xff")), Err(NotPortable::InvalidUtf8(2)));
+        assert_eq!(quotemeta_ash("C1 \u{85}"), Err(NotPortable::Control(3)));
+        assert_eq!(quotemeta_ash("\u{9b}31m"), Err(NotPortable::Control(0)));
+        let bidis =
+            ['\u{61c}', '\u{200e}', '\u{200f}', '\u{202a}', '\u{202e}', '\u{2066}', '\u{2069}'];
+        for bidi in bidis {
+            assert_eq!(quotemeta_ash(format!("x{bidi}y")), Err(NotPortable::Bidi(1)));
+        }
+        assert_eq!(quotemeta_ash("\u{2070}").expect("representable"), "'\u{2070}'");
+        // The first problem is reported, whatever its kind.
+        assert_eq!(quotemeta_ash(OsStr::from_bytes(b"\n\xff")), Err(NotPortable::Control(0)));
+        assert_eq!(quotemeta_ash(OsStr::from_bytes(b"\xff\n")), Err(NotPortable::InvalidUtf8(0)));
+        let bidi_then_invalid = OsStr::from_bytes(b"a\xe2\x80\xae\xff\n");
+        assert_eq!(quotemeta_ash(bidi_then_invalid), Err(NotPortable::Bidi(1)));
+        assert_eq!(NotPortable::Control(3).to_string(), "control character at offset 3");
+    }
+
+    /// Splits the input into words as zsh does. This is the same as bash, except that a word
+    /// starting with an unquoted `=` is replaced with the path of a command, so this refuses it.
+    fn zsh_words(s: &str) -> Vec<Vec<u8>> {
+        for word in s.split(' ') {
+            assert!(!word.starts_with('='), "zsh would expand {:?}", word);
+        }
+        shell_words(s)
+    }
+
+    #[test]
+    fn test_quotemeta_bash_zsh() {
+        assert_eq!(&quotemeta_bash_zsh(""), "''");
+        assert_eq!(&quotemeta_bash_zsh("=ls"), "'=ls'");
+        assert_eq!(&quotemeta_bash_zsh("a b"), "'a b'");
+        assert_eq!(&quotemeta_bash_zsh("=a b"), "'=a b'");
+        assert_eq!(&quotemeta_bash_zsh("=\n"), r"$'=\012'");
+        assert_eq!(&quotemeta_bash_zsh("a=b"), "a=b");
+        let inputs: [&[u8]; 9] =
+            [b"", b"=ls", b"a b", b"a=b", b"~user", b"^x#", b"it's", b"=\xff", b"./=x y"];
+        for &s in &inputs {
+            let quoted = quotemeta_bash_zsh(OsStr::from_bytes(s));
+            assert_eq!(shell_words(&quoted), [s], "{quoted:?}");
+            assert_eq!(zsh_words(&quoted), [s], "{quoted:?}");
+        }
+    }
+
+    #[test]
+    fn test_quotemeta_dotenv() {
+        let dotenv = |key, value: &str| quotemeta_dotenv(key, value).expect("valid key");
+        assert_eq!(dotenv("URL", "https://example.com/"), "URL=https://example.com/");
+        assert_eq!(dotenv("EMPTY", ""), "EMPTY=");
+        assert_eq!(dotenv("NAME", "John Smith"), r#"NAME="John Smith""#);
+        assert_eq!(dotenv("LINES", "one\ntwo\r\n"), r#"LINES="one\ntwo\r\n""#);
+        assert_eq!(dotenv("TAG", "#1"), r##"TAG="#1""##);
+        assert_eq!(dotenv("SAY", r#"say "hi""#), r#"SAY="say \"hi\"""#);
+        assert_eq!(dotenv("RAW", r"C:\$HOME"), r#"RAW="C:\\\$HOME""#);
+        assert_eq!(dotenv("DIR", r"C:\new dir"), r"DIR='C:\new dir'");
+        assert_eq!(dotenv("DIR", r"C:\\root #1"), r"DIR='C:\\root #1'");
+        // A backslash before a line break is safe, since the break is written as `\n` in turn.
+        assert_eq!(dotenv("CONT", "a\\\n"), r#"CONT="a\\\n""#);
+        for value in [r"C:\new's", "C:\\new\n", "\r\\r"] {
+            let offset = value.find('\\').expect("has a backslash");
+            assert_eq!(quotemeta_dotenv("DIR", value), Err(DotenvError::Unrepresentable(offset)));
+        }
+        let invalid = quotemeta_dotenv("BAD", OsStr::from_bytes(b"\xff"));
+        assert_eq!(invalid.expect("valid key"), "BAD=\"\u{fffd}\"");
+        let invalid_key = DotenvError::InvalidKey(InvalidIdentifier(String::from("my-key")));
+        assert_eq!(quotemeta_dotenv("my-key", "x"), Err(invalid_key));
+    }
+
+    #[test]
+    fn test_quotemeta_bash_array() {
+        let array =
+            quotemeta_bash_array("args", ["ls", "", "my file", "tab\there"]).expect("valid name");
+        assert_eq!(&array, r"args=( ls '' 'my file' $'tab\011here' )");
+        let body = array.strip_prefix("args=(").and_then(|rest| rest.strip_suffix(')'));
+        let words = shell_words(body.expect("args=(...)"));
+        assert_eq!(words, [&b"ls"[..], b"", b"my file", b"tab\there"]);
+        assert_eq!(quotemeta_bash_array("_", [""]).expect("valid name"), "_=( '' )");
+        assert_eq!(
+            quotemeta_bash_array("empty", Vec::<&str>::new()).expect("valid name"),
+            "empty=()"
+        );
+        assert_eq!(
+            quotemeta_bash_array("my-args", ["x"]),
+            Err(InvalidIdentifier(String::from("my-args")))
+        );
+    }
+
+    #[test]
+    fn test_quotemeta_var_q() {
+        assert_eq!(quotemeta_var_q("HOME").expect("valid name"), "\"${HOME@Q}\"");
+        assert_eq!(quotemeta_var_q("_x1").expect("valid name"), "\"${_x1@Q}\"");
+        assert_eq!(quotemeta_var_q("1x"), Err(InvalidIdentifier(String::from("1x"))));
+        assert_eq!(quotemeta_var_q("a}b"), Err(InvalidIdentifier(String::from("a}b"))));
+        assert_eq!(quotemeta_var_q(""), Err(InvalidIdentifier(String::new())));
+    }
+}
@@ -0,0 +1,206 @@
+//! Filling in a command template with quoted arguments.
+
+use crate::{quotemeta, script::is_identifier};
+use std::{cmp::Ordering, error::Error, fmt, path::Path};
+
+/// The error returned by [`quotemeta_fill`] when the number of arguments does not match the
+/// number of holes in the template.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum TemplateError {
+    /// There were fewer arguments than holes.
+    TooFewArgs {
+        /// The number of `{}` holes in the template.
+        holes: usize,
+        /// The number of arguments given.
+        args: usize,
+    },
+    /// There were more arguments than holes.
+    TooManyArgs {
+        /// The number of `{}` holes in the template.
+        holes: usize,
+        /// The number of arguments given.
+        args: usize,
+    },
+}
+
+impl fmt::Display for TemplateError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            Self::TooFewArgs { holes, args } => {
+                write!(f, "template has {holes} holes but only {args} arguments were given")
+            }
+            Self::TooManyArgs { holes, args } => {
+                write!(f, "template has only {holes} holes but {args} arguments were given")
+            }
+        }
+    }
+}
+
+impl Error for TemplateError {}
+
+/// Fills in each `{}` in the template with the next argument, quoted by [`quotemeta`].
+///
+/// This is a `format!` for shell commands: the template is trusted, and is copied through as-is so
+/// that its author controls the structure of the command, with pipes, redirections and so on,
+/// while the arguments are not, and are always quoted so that they cannot inject anything. An
+/// empty argument is written as `''`. A literal `{}` is written in the template as `{{}}`; any
+/// other braces, such as those in `${HOME}` or `{a,b}`, are copied through unchanged.
+///
+/// # Errors
+///
+/// Returns a [`TemplateError`] unless there are exactly as many arguments as holes.
+///
+/// ```
+/// use quotemeta::quotemeta_fill;
+///
+/// let command = quotemeta_fill("grep -r {} {} | wc -l", &["a b", "My Documents"]).unwrap();
+/// assert_eq!(&command, "grep -r 'a b' 'My Documents' | wc -l");
+/// let find = quotemeta_fill("find {} -exec rm {{}} +", &["/tmp/my dir"]).unwrap();
+/// assert_eq!(&find, "find '/tmp/my dir' -exec rm {} +");
+/// ```
+pub fn quotemeta_fill(
+    template: &str, args: &[impl AsRef<Path>],
+) -> Result<String, TemplateError> {
+    let (mut out, mut rest) = (String::with_capacity(template.len()), template);
+    let (mut holes, mut remaining) = (0, args.iter());
+    while !rest.is_empty() {
+        if let Some(tail) = rest.strip_prefix("{{}}") {
+            out.push_str("{}");
+            rest = tail;
+        } else if let Some(tail) = rest.strip_prefix("{}") {
+            holes += 1;
+            match remaining.next().map(quotemeta) {
+                Some(word) if word.is_empty() => out.push_str("''"),
+                Some(word) => out.push_str(&word),
+                None => {}
+            }
+            rest = tail;
+        } else {
+            let mut chars = rest.chars();
+            out.extend(chars.next());
+            rest = chars.as_str();
+        }
+    }
+    match holes.cmp(&args.len()) {
+        Ordering::Equal => Ok(out),
+        Ordering::Greater => Err(TemplateError::TooFewArgs { holes, args: args.len() }),
+        Ordering::Less => Err(TemplateError::TooManyArgs { holes, args: args.len() }),
+    }
+}
+
+/// Returns the length of the variable reference at the start of `s`, if any: `$NAME` or
+/// `${NAME}`, where `NAME` is a valid shell variable name.
+fn variable_len(s: &str) -> Option<usize> {
+    let rest = s.strip_prefix('$')?;
+    let (name, len) = if let Some(braced) = rest.strip_prefix('{') {
+        let end = braced.find('}')?;
+        (braced.get(.. end)?, end + 3)
+    } else {
+        let end = rest.find(|c: char| !c.is_ascii_alphanumeric() && c != '_').unwrap_or(rest.len());
+        (rest.get(.. end)?, end + 1)
+    };
+    is_identifier(name.as_bytes()).then_some(len)
+}
+
+/// Shell-quotes the string, except for references to shell variables, which are left to be
+/// expanded.
+///
+/// The only references recognised are `$NAME` and `${NAME}`, where `NAME` is an ASCII letter or
+/// underscore followed by any number of ASCII letters, digits and underscores; `$NAME` takes the
+/// longest such name. Anything else is quoted as literal text by [`quotemeta`], including a `$`
+/// which does not start one of these, positional and special parameters such as `$1` and `$@`,
+/// and other expansions such as `${NAME:-default}`, `$(command)` and `$((arithmetic))`. Each
+/// reference is put in double quotes, so that its value is used as-is rather than being split
+/// into words or expanded as a glob, and the pieces are written one after another to make a
+/// single word. An empty string is written as `''`.
+///
+/// This is unlike [`quotemeta_fill`], where the template is trusted and only the arguments are
+/// quoted: here the string is only semi-trusted, since whoever writes it chooses which variables
+/// the shell expands. It cannot run commands, but it can read any variable in the environment of
+/// the script, such as one holding a secret, and a value starting with `-` may be taken as an
+/// option. Only use it where that is acceptable.
+///
+/// ```
+/// use quotemeta::quotemeta_keep_vars;
+///
+/// assert_eq!(&quotemeta_keep_vars("$HOME/my file"), r#""$HOME"'/my file'"#);
+/// assert_eq!(&quotemeta_keep_vars("$(reboot) costs $5"), "'$(reboot) costs $5'");
+/// ```
+#[must_use]
+pub fn quotemeta_keep_vars(s: &str) -> String {
+    let (mut out, mut literal, mut rest) = (String::new(), String::new(), s);
+    while !rest.is_empty() {
+        let reference = variable_len(rest).and_then(|len| rest.split_at_checked(len));
+        if let Some((variable, tail)) = reference {
+            out.push_str(&quotemeta(&literal));
+            literal.clear();
+            out.push('"');
+            out.push_str(variable);
+            out.push('"');
+            rest = tail;
+        } else {
+            let mut chars = rest.chars();
+            literal.extend(chars.next());
+            rest = chars.as_str();
+        }
+    }
+    out.push_str(&quotemeta(&literal));
+    if out.is_empty() { String::from("''") } else { out }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{quotemeta, quotemeta_fill, quotemeta_keep_vars, tests::shell_words, TemplateError};
+
+    #[test]
+    fn test_quotemeta_fill() {
+        let fill = |template, args: &[&str]| quotemeta_fill(template, args).expect("as many args");
+        let command = fill("grep {} {}", &["hello world", "my file.txt"]);
+        assert_eq!(&command, "grep 'hello world' 'my file.txt'");
+        assert_eq!(shell_words(&command), [&b"grep"[..], b"hello world", b"my file.txt"]);
+        // The template is trusted, but the arguments are not.
+        assert_eq!(
+            fill("echo $HOME {} >{}", &["$(reboot)", "out; rm -rf /"]),
+            "echo $HOME '$(reboot)' >'out; rm -rf /'"
+        );
+        assert_eq!(&fill("printf %s {}", &[""]), "printf %s ''");
+        assert_eq!(&fill("{}{}", &["a", "b c"]), "a'b c'");
+        assert_eq!(&fill("{{}} {} ${x} {a,b}", &["x"]), "{} x ${x} {a,b}");
+        assert_eq!(fill("caf\u{e9} {}", &["\u{e9}"]), "caf\u{e9} $'\\303\\251'");
+        assert_eq!(&fill("", &[]), "");
+    }
+
+    #[test]
+    fn test_quotemeta_fill_errors() {
+        assert_eq!(
+            quotemeta_fill("grep {} {}", &["x"]),
+            Err(TemplateError::TooFewArgs { holes: 2, args: 1 })
+        );
+        assert_eq!(
+            quotemeta_fill("grep {{}} {}", &["x", "y"]),
+            Err(TemplateError::TooManyArgs { holes: 1, args: 2 })
+        );
+        assert_eq!(
+            TemplateError::TooFewArgs { holes: 2, args: 1 }.to_string(),
+            "template has 2 holes but only 1 arguments were given"
+        );
+    }
+
+    #[test]
+    fn test_quotemeta_keep_vars() {
+        assert_eq!(&quotemeta_keep_vars("prefix $HOME suffix"), r#"'prefix '"$HOME"' suffix'"#);
+        assert_eq!(&quotemeta_keep_vars("${VAR}/path with space"), r#""${VAR}"'/path with space'"#);
+        assert_eq!(&quotemeta_keep_vars("$HOME"), r#""$HOME""#);
+        assert_eq!(&quotemeta_keep_vars("$A$B_1-x"), r#""$A""$B_1"-x"#);
+        assert_eq!(&quotemeta_keep_vars("${HOME}s"), r#""${HOME}"s"#);
+        assert_eq!(&quotemeta_keep_vars(""), "''");
+        assert_eq!(&quotemeta_keep_vars("plain"), "plain");
+        // Anything which is not a plain variable reference is quoted.
+        for s in ["$", "$1", "$@", "$$", "${1}", "${A#x}", "${A", "${}", "$(id)", "$((1+1))"] {
+            assert_eq!(quotemeta_keep_vars(s), quotemeta(s), "{s}");
+        }
+        assert_eq!(&quotemeta_keep_vars("it's $USER's"), r#"$'it\'s '"$USER"$'\'s'"#);
+        assert_eq!(&quotemeta_keep_vars("caf\u{e9} $X"), r#"$'caf\303\251 '"$X""#);
+    }
+}
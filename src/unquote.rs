@@ -0,0 +1,432 @@
+//! Reversing the quoting, to get back the original bytes.
+
+use crate::{is_safe, quotemeta};
+#[cfg(unix)] use std::os::unix::ffi::OsStringExt;
+use std::{
+    error::Error,
+    ffi::OsString,
+    fmt,
+    iter::{Enumerate, Peekable},
+    path::Path,
+    slice,
+};
+
+/// The error returned when a string is not in a form which [`unquotemeta`] understands.
+///
+/// Each variant holds the byte offset in the input at which the problem was found.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum UnquoteError {
+    /// A character which should have been quoted appeared outside quotes.
+    Unexpected(usize),
+    /// An escape sequence which this crate never produces appeared within `$'...'`.
+    BadEscape(usize),
+    /// A quoted string which starts here is never closed.
+    Unterminated(usize),
+}
+
+impl fmt::Display for UnquoteError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            Self::Unexpected(offset) => {
+                write!(f, "unexpected unquoted character at offset {offset}")
+            }
+            Self::BadEscape(offset) => write!(f, "unsupported escape sequence at offset {offset}"),
+            Self::Unterminated(offset) => write!(f, "unterminated quote at offset {offset}"),
+        }
+    }
+}
+
+impl Error for UnquoteError {}
+
+type Input<'input> = Peekable<Enumerate<slice::Iter<'input, u8>>>;
+
+/// Returns the byte for a named escape such as `\n` within `$'...'`, which only the lenient
+/// parser accepts.
+const fn c_named_escape(letter: u8) -> u8 {
+    match letter {
+        b'a' => 0x07,
+        b'b' => 0x08,
+        b'e' | b'E' => 0x1b,
+        b'f' => 0x0c,
+        b'n' => b'\n',
+        b'r' => b'\r',
+        b't' => b'\t',
+        b'v' => 0x0b,
+        _ => letter,
+    }
+}
+
+/// Decodes the body of a `$'...'` string, up to and including the closing quote. If `lenient` is
+/// set, the other escapes which bash understands are accepted too, except for `\u` and `\U`.
+fn unquote_c(
+    input: &mut Input<'_>, start: usize, out: &mut Vec<u8>, lenient: bool,
+) -> Result<(), UnquoteError> {
+    loop {
+        match input.next().ok_or(UnquoteError::Unterminated(start))? {
+            (_, b'\'') => return Ok(()),
+            (offset, b'\\') => {
+                let bad = UnquoteError::BadEscape(offset);
+                match input.next().ok_or(UnquoteError::Unterminated(start))? {
+                    (_, &first @ b'0' ..= b'7') if lenient => {
+                        let mut value = first - b'0';
+                        for _ in 0 .. 2 {
+                            let Some(&(_, &digit @ b'0' ..= b'7')) = input.peek() else { break };
+                            let _ = input.next();
+                            value = value.wrapping_mul(8).wrapping_add(digit - b'0');
+                        }
+                        out.push(value);
+                    }
+                    (_, &first @ b'0' ..= b'3') => {
+                        let mut value = first - b'0';
+                        for _ in 0 .. 2 {
+                            let Some((_, &digit @ b'0' ..= b'7')) = input.next() else {
+                                return Err(bad);
+                            };
+                            value = value * 8 + (digit - b'0');
+                        }
+                        out.push(value);
+                    }
+                    (_, b'x') if lenient => {
+                        let mut value = None;
+                        for _ in 0 .. 2 {
+                            let Some(digit) = input.peek().and_then(|&(_, &c)| hex_digit(c)) else {
+                                break;
+                            };
+                            let _ = input.next();
+                            value = Some(value.unwrap_or(0) * 16 + digit);
+                        }
+                        out.push(value.ok_or(bad)?);
+                    }
+                    (_, &literal @ (b'\\' | b'\'')) => out.push(literal),
+                    (_, &letter) if lenient && b"abeEfnrtv\"?".contains(&letter) => {
+                        out.push(c_named_escape(letter));
+                    }
+                    (_, b'c') => match input.next() {
+                        Some((_, b'?')) => out.push(0x7f),
+                        Some((_, &letter @ (b'@' ..= b'[' | b']' ..= b'_'))) => {
+                            out.push(letter & 0x1f);
+                        }
+                        _ => return Err(bad),
+                    },
+                    _ => return Err(bad),
+                }
+            }
+            (_, &other) => out.push(other),
+        }
+    }
+}
+
+/// Returns the value of a hexadecimal digit.
+const fn hex_digit(c: u8) -> Option<u8> {
+    match c {
+        b'0' ..= b'9' => Some(c - b'0'),
+        b'a' ..= b'f' => Some(c - b'a' + 10),
+        b'A' ..= b'F' => Some(c - b'A' + 10),
+        _ => None,
+    }
+}
+
+/// Decodes the body of a `"..."` string, up to and including the closing quote. Anything which
+/// would be expanded is rejected.
+fn unquote_double(
+    input: &mut Input<'_>, start: usize, out: &mut Vec<u8>,
+) -> Result<(), UnquoteError> {
+    loop {
+        match input.next().ok_or(UnquoteError::Unterminated(start))? {
+            (_, b'"') => return Ok(()),
+            (_, b'\\') => match input.next().ok_or(UnquoteError::Unterminated(start))? {
+                (_, b'\n') => {}
+                (_, &escaped @ (b'$' | b'`' | b'"' | b'\\')) => out.push(escaped),
+                (_, &other) => out.extend([b'\\', other]),
+            },
+            (offset, b'$' | b'`') => return Err(UnquoteError::Unexpected(offset)),
+            (_, &other) => out.push(other),
+        }
+    }
+}
+
+/// Parses a shell word, as for [`unquotemeta`] or, if `lenient` is set, [`unquotemeta_lenient`].
+fn unquote_with(s: &str, lenient: bool) -> Result<OsString, UnquoteError> {
+    let bytes = s.as_bytes();
+    let mut input = bytes.iter().enumerate().peekable();
+    let mut out = Vec::with_capacity(s.len());
+    while let Some((offset, &c)) = input.next() {
+        let prev = offset.checked_sub(1).and_then(|prev| bytes.get(prev));
+        match c {
+            b'\'' => loop {
+                match input.next().ok_or(UnquoteError::Unterminated(offset))? {
+                    (_, b'\'') => break,
+                    (_, &quoted) => out.push(quoted),
+                }
+            },
+            b'$' if matches!(input.peek(), Some((_, b'\''))) => {
+                let _ = input.next();
+                unquote_c(&mut input, offset, &mut out, lenient)?;
+            }
+            b'\\' if matches!(input.peek(), Some((_, b'\''))) => {
+                let _ = input.next();
+                out.push(b'\'');
+            }
+            b'"' if lenient => unquote_double(&mut input, offset, &mut out)?,
+            b'\\' if lenient => match input.next().ok_or(UnquoteError::BadEscape(offset))? {
+                (_, b'\n') => {}
+                (_, &escaped) => out.push(escaped),
+            },
+            b'%' if lenient => out.push(c),
+            b'#' if lenient && prev.is_some() => out.push(c),
+            b'~' if lenient && !matches!(prev, None | Some(b':' | b'=')) => out.push(c),
+            _ if is_safe(c) => out.push(c),
+            _ => return Err(UnquoteError::Unexpected(offset)),
+        }
+    }
+    Ok(OsString::from_vec(out))
+}
+
+/// Reverses [`quotemeta`], turning a single quoted word back into the bytes it represents.
+///
+/// This understands everything which this crate's shell quoting produces: bare text, `'...'`,
+/// `$'...'` with three-digit octal, `\\`, `\'` and `\cX` escapes, the `'\''` idiom, and any
+/// concatenation of those. It is deliberately strict, and rejects other escapes such as `\n` or
+/// `\x0a` even though the shell accepts them, and any unquoted character which should have been
+/// quoted.
+///
+/// # Errors
+///
+/// Returns an [`UnquoteError`] if the input is not in a form which this crate would produce.
+///
+/// ```
+/// use quotemeta::{quotemeta, unquotemeta};
+///
+/// assert_eq!(unquotemeta(r"$'tab\011here'").unwrap(), "tab\there");
+/// assert_eq!(unquotemeta(&quotemeta("it's")).unwrap(), "it's");
+/// assert!(unquotemeta("a b").is_err());
+/// ```
+pub fn unquotemeta(s: &str) -> Result<OsString, UnquoteError> { unquote_with(s, false) }
+
+/// Quotes the given [`Path`] and then unquotes it again.
+///
+/// This exercises the whole pipeline from a path to its bytes, through [`quotemeta`] and
+/// [`unquotemeta`], and back to an [`OsString`], and so is handy in tests to check that paths
+/// survive quoting intact: the result should always equal `p.as_os_str()`.
+///
+/// # Panics
+///
+/// Panics if [`unquotemeta`] rejects the output of [`quotemeta`], which would be a bug in this
+/// crate.
+///
+/// ```
+/// use quotemeta::quote_unquote_path;
+/// use std::path::Path;
+///
+/// let path = Path::new("/tmp/it's a\ttest");
+/// assert_eq!(quote_unquote_path(path), path.as_os_str());
+/// ```
+#[must_use]
+#[cfg_attr(
+    feature = "clippy-insane",
+    expect(clippy::expect_used, reason = "a failure is a bug in this crate, as documented")
+)]
+pub fn quote_unquote_path(p: &Path) -> OsString {
+    unquotemeta(&quotemeta(p)).expect("quotemeta output is always accepted by unquotemeta")
+}
+
+/// Turns a single shell word back into the bytes it represents, accepting more of the shell's
+/// quoting than [`unquotemeta`] does.
+///
+/// This is for parsing commands written by people rather than only those which this crate
+/// produces.
+///
+/// As well as everything which [`unquotemeta`] accepts, this understands:
+///
+/// - backslash escapes outside quotes, as in `a\ b`, including the backslash-newline which
+///   continues a line;
+/// - `"..."`, within which `\$`, `` \` ``, `\"`, `\\` and backslash-newline are escapes and any
+///   other backslash is taken literally;
+/// - the other escapes which bash understands within `$'...'`, such as `\n`, `\t`, `\E`, `\x0a`
+///   and octal with fewer than three digits;
+/// - an unquoted `%`, and an unquoted `#` or `~` where it is not special.
+///
+/// It only parses quoting, and never expands anything: a `$` or `` ` `` within `"..."` is
+/// rejected rather than taken literally, as is any unquoted character which the shell would
+/// treat specially, such as a space, `$`, `*` or a leading `~`. The `\u` and `\U` escapes are
+/// rejected too, since what they produce depends on the locale.
+///
+/// # Errors
+///
+/// Returns an [`UnquoteError`] if the input is not in one of the forms described.
+///
+/// ```
+/// use quotemeta::unquotemeta_lenient;
+///
+/// assert_eq!(unquotemeta_lenient(r#"my\ "big file"$'\x21\n'"#).unwrap(), "my big file!\n");
+/// assert!(unquotemeta_lenient(r#""$HOME""#).is_err());
+/// ```
+pub fn unquotemeta_lenient(s: &str) -> Result<OsString, UnquoteError> { unquote_with(s, true) }
+
+/// Re-quotes a shell word in the canonical form given by [`quotemeta`], so that words which are
+/// quoted differently but mean the same thing compare equal, as in snapshot tests.
+///
+/// This accepts everything which [`unquotemeta_lenient`] does, so the output of `printf %q` and
+/// of [`quotemeta_printf_q`](crate::quotemeta_printf_q) can be normalized, along with that of
+/// every [`Quoter`](crate::Quoter).
+///
+/// # Errors
+///
+/// Returns an [`UnquoteError`] if [`unquotemeta_lenient`] does.
+///
+/// ```
+/// use quotemeta::normalize_quoted;
+///
+/// for s in &[r#""a b""#, r"a\ b", "$'a b'", "'a'' b'"] {
+///     assert_eq!(normalize_quoted(s).unwrap(), "'a b'");
+/// }
+/// ```
+pub fn normalize_quoted(s: &str) -> Result<String, UnquoteError> {
+    unquotemeta_lenient(s).map(quotemeta)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        normalize_quoted, quote_unquote_path, quotemeta, quotemeta_printf_q, unquotemeta,
+        unquotemeta_lenient, EscapeFormat, QuoteStyle, Quoter, UnquoteError,
+    };
+    #[cfg(unix)] use std::os::unix::ffi::OsStrExt;
+    use std::{ffi::OsStr, path::Path};
+
+    #[test]
+    fn test_unquotemeta() {
+        assert_eq!(unquotemeta("").expect("parses"), "");
+        assert_eq!(unquotemeta("''").expect("parses"), "");
+        assert_eq!(unquotemeta("/bin/cat").expect("parses"), "/bin/cat");
+        assert_eq!(unquotemeta("'a b'").expect("parses"), "a b");
+        assert_eq!(unquotemeta(r"./'a b'$'\012'more").expect("parses"), "./a b\nmore");
+        assert_eq!(unquotemeta(r"'it'\''s'").expect("parses"), "it's");
+        assert_eq!(unquotemeta(r"$'\cM\c@\c?\\\''").expect("parses"), "\r\0\x7f\\'");
+        assert_eq!(unquotemeta(r"$'\377'").expect("parses"), OsStr::from_bytes(b"\xff"));
+        assert_eq!(unquotemeta("$'caf\u{e9}'").expect("parses"), "caf\u{e9}");
+
+        assert_eq!(unquotemeta("a b"), Err(UnquoteError::Unexpected(1)));
+        assert_eq!(unquotemeta("~"), Err(UnquoteError::Unexpected(0)));
+        assert_eq!(unquotemeta("$HOME"), Err(UnquoteError::Unexpected(0)));
+        assert_eq!(unquotemeta("x'abc"), Err(UnquoteError::Unterminated(1)));
+        assert_eq!(unquotemeta(r"$'\'"), Err(UnquoteError::Unterminated(0)));
+        assert_eq!(unquotemeta(r"$'\n'"), Err(UnquoteError::BadEscape(2)));
+        assert_eq!(unquotemeta(r"$'\12'"), Err(UnquoteError::BadEscape(2)));
+        assert_eq!(unquotemeta(r"$'\400'"), Err(UnquoteError::BadEscape(2)));
+        assert_eq!(unquotemeta(r"$'\ca'"), Err(UnquoteError::BadEscape(2)));
+        assert_eq!(
+            UnquoteError::BadEscape(2).to_string(),
+            "unsupported escape sequence at offset 2"
+        );
+    }
+
+    #[test]
+    fn test_unquote_quoter_output() {
+        let quoters = [
+            Quoter::new(),
+            Quoter::new().style(QuoteStyle::IsolateDangerous),
+            Quoter::new().escape_format(EscapeFormat::CtrlLetter),
+            Quoter::new().preserve_dot_prefix(false),
+        ];
+        let inputs: &[&[u8]] = &[b"", b"x", b"a b", b"it's", b"\x00\x1c\x7f\xff", b"./-\n\\"];
+        for quoter in &quoters {
+            for &s in inputs {
+                let s = OsStr::from_bytes(s);
+                assert_eq!(unquotemeta(&quoter.quote(s)).expect("parses"), s);
+            }
+        }
+    }
+
+    #[test]
+    fn test_quote_unquote_path() {
+        let paths: &[&[u8]] = &[
+            b"/",
+            b"relative/path",
+            b"../up/../and down",
+            b"/tmp/it's \"quoted\"",
+            b"/home/user/\xe9t\xe9.txt",
+            b"/odd/\xff\xfe/\x80",
+            b"a\nb\tc\rd\x1be",
+            b"~user/*.{rs,toml}",
+            b"\xf0\x9f\xa6\x80/\xc3\xa9",
+        ];
+        for &bytes in paths {
+            let path = Path::new(OsStr::from_bytes(bytes));
+            assert_eq!(quote_unquote_path(path), path.as_os_str());
+        }
+    }
+
+    #[test]
+    fn test_unquotemeta_lenient() {
+        let cases: &[(&str, &[u8])] = &[
+            ("plain", b"plain"),
+            (r"my\ file", b"my file"),
+            (
+                r#"'single'\ "double \"quoted\" \n"$'\x41\n\t\0\101\e'"#,
+                b"single double \"quoted\" \\nA\n\t\0A\x1b",
+            ),
+            (r#""it's"'"'"#, b"it's\""),
+            (r"$'\xff\xFe\x7\7\77'", b"\xff\xfe\x07\x07?"),
+            ("a\\\nb", b"ab"),
+            (r"100%\ a#b~", b"100% a#b~"),
+            (r#""""#, b""),
+        ];
+        for &(s, bytes) in cases {
+            assert_eq!(unquotemeta_lenient(s).expect("parses"), OsStr::from_bytes(bytes), "{s}");
+        }
+        // Everything which the strict parser accepts means the same to the lenient one.
+        let inputs: &[&[u8]] = &[b"a b", b"it's", b"\x1b[0m\xff", b"./x y", b"~/#1"];
+        for &s in inputs {
+            let s = OsStr::from_bytes(s);
+            assert_eq!(unquotemeta_lenient(&quotemeta(s)).expect("parses"), s);
+        }
+
+        // Nothing is expanded.
+        assert_eq!(unquotemeta_lenient("$HOME"), Err(UnquoteError::Unexpected(0)));
+        assert_eq!(unquotemeta_lenient(r#""a $b""#), Err(UnquoteError::Unexpected(3)));
+        assert_eq!(unquotemeta_lenient("a b"), Err(UnquoteError::Unexpected(1)));
+        assert_eq!(unquotemeta_lenient("*.txt"), Err(UnquoteError::Unexpected(0)));
+        assert_eq!(unquotemeta_lenient(r"$'\u00e9'"), Err(UnquoteError::BadEscape(2)));
+    }
+
+    #[test]
+    fn test_normalize_quoted() {
+        for s in &[r#""a b""#, r"a\ b", "$'a b'", "'a b'", r"'a'\ b", r#"a" "b"#, r"$'a\x20b'"] {
+            assert_eq!(normalize_quoted(s).expect("parses"), "'a b'", "{s}");
+        }
+        assert_eq!(normalize_quoted("/bin/cat").expect("parses"), "/bin/cat");
+        assert_eq!(normalize_quoted(r#""it's""#).expect("parses"), r"$'it\'s'");
+        assert_eq!(
+            normalize_quoted(r#""\$HOME \"x\" \n""#).expect("parses"),
+            r#"$'$HOME "x" \\n'"#
+        );
+        assert_eq!(normalize_quoted("a\\\nb").expect("parses"), "ab");
+        assert_eq!(
+            normalize_quoted(r"$'\E[0m\t\7\77\x7'").expect("parses"),
+            r"$'\033[0m\011\007?\007'"
+        );
+        assert_eq!(normalize_quoted(r"100%\ a#b\ ~").expect("parses"), "'100% a#b ~'");
+
+        assert_eq!(normalize_quoted(r#""$HOME""#), Err(UnquoteError::Unexpected(1)));
+        assert_eq!(normalize_quoted(r#""`id`""#), Err(UnquoteError::Unexpected(1)));
+        assert_eq!(normalize_quoted(r#""abc"#), Err(UnquoteError::Unterminated(0)));
+        assert_eq!(normalize_quoted("#comment"), Err(UnquoteError::Unexpected(0)));
+        assert_eq!(normalize_quoted("~root"), Err(UnquoteError::Unexpected(0)));
+        assert_eq!(normalize_quoted("a=~"), Err(UnquoteError::Unexpected(2)));
+        assert_eq!(normalize_quoted("a\\"), Err(UnquoteError::BadEscape(1)));
+        assert_eq!(normalize_quoted(r"$'\u00e9'"), Err(UnquoteError::BadEscape(2)));
+        assert_eq!(normalize_quoted(r"$'\xg'"), Err(UnquoteError::BadEscape(2)));
+        // The strict parser still rejects the extra forms.
+        assert_eq!(unquotemeta(r"a\ b"), Err(UnquoteError::Unexpected(1)));
+
+        // Whatever printf %q produces normalizes to what quotemeta produces.
+        let inputs: &[&[u8]] =
+            &[b"", b"a b", b"~/it's #1", b"x=~", b"tab\there", b"\x1b\xff", b"{a,b}"];
+        for &s in inputs {
+            let s = OsStr::from_bytes(s);
+            assert_eq!(normalize_quoted(&quotemeta_printf_q(s)).expect("parses"), quotemeta(s));
+        }
+    }
+}
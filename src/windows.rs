@@ -0,0 +1,314 @@
+//! Quoting for Windows command lines, which are parsed by the program rather than by a shell,
+//! and checks for filenames which Windows would have trouble with.
+
+use crate::quotemeta;
+#[cfg(unix)] use std::os::unix::ffi::OsStrExt;
+use std::{iter, path::Path};
+
+/// Which set of rules a Windows program uses to split its command line into arguments, for
+/// [`quotemeta_windows`].
+///
+/// Both rulesets agree on the basics: arguments are separated by spaces or tabs, `"` begins or
+/// ends a quoted section in which spaces are literal, `2n` backslashes followed by a `"` become
+/// `n` backslashes and a quote delimiter, `2n+1` backslashes followed by a `"` become `n`
+/// backslashes and a literal `"`, and other backslashes are literal. They differ in how they
+/// treat a doubled `""` within a quoted section.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum WindowsRules {
+    /// The rules used by the Microsoft C runtime (since 2008) to build `argv` for `main`. A `""`
+    /// within a quoted section is a literal `"`, and the section continues. So `"a""b c"` is the
+    /// single argument `a"b c`.
+    CrtArgv,
+    /// The rules used by `CommandLineToArgvW`. A `""` within a quoted section is a literal `"`, but
+    /// ends the section. So `"a""b c"` is the two arguments `a"b` and `c`.
+    CommandLineToArgvW,
+}
+
+/// Quotes the given argument for a Windows command line, following the given `rules`.
+///
+/// An argument which is non-empty and contains no whitespace or `"` is returned as-is. Otherwise
+/// it is wrapped in `"`, with any backslashes before a `"` (including the closing one) doubled.
+/// Embedded quotes are written as `""` under [`WindowsRules::CrtArgv`], and as `\"` under
+/// [`WindowsRules::CommandLineToArgvW`] where `""` would end the quoted section.
+///
+/// Note that this is for the program's own parsing of its command line, and does not protect
+/// against `cmd.exe` metacharacters such as `%`, `^` or `&`.
+///
+/// ```
+/// use quotemeta::{quotemeta_windows, WindowsRules::{CommandLineToArgvW, CrtArgv}};
+///
+/// assert_eq!(quotemeta_windows(r"C:\Windows", CrtArgv), r"C:\Windows");
+/// assert_eq!(quotemeta_windows(r"C:\Program Files\", CrtArgv), r#""C:\Program Files\\""#);
+/// assert_eq!(quotemeta_windows(r#"say "hi""#, CrtArgv), r#""say ""hi""""#);
+/// assert_eq!(quotemeta_windows(r#"say "hi""#, CommandLineToArgvW), r#""say \"hi\"""#);
+/// ```
+#[must_use]
+pub fn quotemeta_windows(s: &str, rules: WindowsRules) -> String {
+    if !s.is_empty() && !s.contains([' ', '\t', '\n', '\u{b}', '"']) {
+        return s.to_owned();
+    }
+    let mut out = String::with_capacity(s.len() + 2);
+    out.push('"');
+    let mut backslashes = 0;
+    for c in s.chars() {
+        match c {
+            '\\' => {
+                backslashes += 1;
+                out.push('\\');
+            }
+            '"' => {
+                out.extend(iter::repeat_n('\\', backslashes));
+                backslashes = 0;
+                out.push_str(match rules {
+                    WindowsRules::CrtArgv => r#""""#,
+                    WindowsRules::CommandLineToArgvW => r#"\""#,
+                });
+            }
+            _ => {
+                backslashes = 0;
+                out.push(c);
+            }
+        }
+    }
+    out.extend(iter::repeat_n('\\', backslashes));
+    out.push('"');
+    out
+}
+
+/// A reason why a filename is troublesome on Windows, as reported by [`sanitize_windows_name`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum WindowsNameIssue {
+    /// The name ends with the given character, a dot or space, which Windows silently strips.
+    Trailing(char),
+    /// The name, ignoring any extension, is reserved for a device such as `CON` or `NUL`.
+    Reserved,
+    /// The name contains the given character, which is not allowed in a filename. This is one of
+    /// `<>:"/\|?*` or a control character.
+    IllegalChar(char),
+}
+
+/// Device names which Windows reserves in every directory. The serial and parallel ports take a
+/// digit from 0 to 9 or a superscript `¹`, `²` or `³`, and the console also has `CONIN$` and
+/// `CONOUT$`.
+const RESERVED_NAMES: [&str; 32] = [
+    "CON", "PRN", "AUX", "NUL", "CONIN$", "CONOUT$", "COM0", "COM1", "COM2", "COM3", "COM4", "COM5",
+    "COM6", "COM7", "COM8", "COM9", "COM\u{b9}", "COM\u{b2}", "COM\u{b3}", "LPT0", "LPT1", "LPT2",
+    "LPT3", "LPT4", "LPT5", "LPT6", "LPT7", "LPT8", "LPT9", "LPT\u{b9}", "LPT\u{b2}", "LPT\u{b3}",
+];
+
+const fn is_illegal_on_windows(c: u8) -> bool {
+    matches!(c, 0 ..= 31 | b'<' | b'>' | b':' | b'"' | b'/' | b'\\' | b'|' | b'?' | b'*')
+}
+
+/// Returns whether the part of the name before any extension is a reserved device name.
+fn is_reserved_on_windows(s: &[u8]) -> bool {
+    let stem = s.split(|&c| c == b'.').next().unwrap_or_default().trim_ascii_end();
+    RESERVED_NAMES.iter().any(|name| stem.eq_ignore_ascii_case(name.as_bytes()))
+}
+
+/// Shell-quotes the given filename (a single path component) with [`quotemeta`], and reports
+/// anything about it which would cause trouble on Windows.
+///
+/// The issues are reported in order: a reserved name first, then each distinct illegal character
+/// in the order it first appears, then a trailing dot or space. Use [`windows_safe_name`] to fix
+/// them.
+///
+/// ```
+/// use quotemeta::{sanitize_windows_name, WindowsNameIssue};
+///
+/// assert_eq!(sanitize_windows_name("report.txt"), (String::from("report.txt"), vec![]));
+/// assert_eq!(
+///     sanitize_windows_name("nul.txt"),
+///     (String::from("nul.txt"), vec![WindowsNameIssue::Reserved])
+/// );
+/// ```
+pub fn sanitize_windows_name(s: impl AsRef<Path>) -> (String, Vec<WindowsNameIssue>) {
+    let bytes = s.as_ref().as_os_str().as_bytes();
+    let mut issues = Vec::new();
+    if is_reserved_on_windows(bytes) {
+        issues.push(WindowsNameIssue::Reserved);
+    }
+    for &c in bytes {
+        let issue = WindowsNameIssue::IllegalChar(char::from(c));
+        if is_illegal_on_windows(c) && !issues.contains(&issue) {
+            issues.push(issue);
+        }
+    }
+    if let Some(&c @ (b'.' | b' ')) = bytes.last() {
+        issues.push(WindowsNameIssue::Trailing(char::from(c)));
+    }
+    (quotemeta(s), issues)
+}
+
+/// Returns a version of the given filename which does not have any of the issues reported by
+/// [`sanitize_windows_name`].
+///
+/// Illegal characters are replaced with `_`, trailing dots and spaces are removed, and a reserved
+/// name has a `_` appended to its stem. Bytes which are not valid UTF-8 are replaced with U+FFFD.
+/// If nothing is left, the result is `_`.
+///
+/// ```
+/// use quotemeta::windows_safe_name;
+///
+/// assert_eq!(&windows_safe_name("What? Me worry. "), "What_ Me worry");
+/// assert_eq!(&windows_safe_name("con.txt"), "con_.txt");
+/// ```
+pub fn windows_safe_name(s: impl AsRef<Path>) -> String {
+    let bytes = s.as_ref().as_os_str().as_bytes();
+    let mut name: Vec<u8> =
+        bytes.iter().map(|&c| if is_illegal_on_windows(c) { b'_' } else { c }).collect();
+    while matches!(name.last(), Some(b'.' | b' ')) {
+        let _ = name.pop();
+    }
+    let mut name = String::from_utf8_lossy(&name).into_owned();
+    if is_reserved_on_windows(name.as_bytes()) {
+        let stem_len = name.find('.').unwrap_or(name.len());
+        name.insert(stem_len, '_');
+    }
+    if name.is_empty() {
+        name.push('_');
+    }
+    name
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        quotemeta_windows, sanitize_windows_name, windows_safe_name, WindowsNameIssue,
+        WindowsRules,
+    };
+    use std::iter;
+
+    /// A reference parser for a Windows command line which does not include the program name.
+    #[cfg_attr(
+        feature = "clippy-insane",
+        expect(clippy::integer_division_remainder_used, reason = "backslashes come in pairs")
+    )]
+    fn parse(s: &str, rules: WindowsRules) -> Vec<String> {
+        let (mut args, mut chars) = (Vec::new(), s.chars().peekable());
+        let (mut arg, mut in_quotes): (Option<String>, bool) = (None, false);
+        while let Some(c) = chars.next() {
+            match c {
+                ' ' | '\t' if !in_quotes => args.extend(arg.take()),
+                '\\' => {
+                    let mut backslashes = 1;
+                    while chars.next_if_eq(&'\\').is_some() {
+                        backslashes += 1;
+                    }
+                    let out = arg.get_or_insert_with(String::new);
+                    if chars.peek() == Some(&'"') {
+                        out.extend(iter::repeat_n('\\', backslashes / 2));
+                        if backslashes % 2 == 1 {
+                            out.push('"');
+                            let _ = chars.next();
+                        }
+                    } else {
+                        out.extend(iter::repeat_n('\\', backslashes));
+                    }
+                }
+                '"' => {
+                    let out = arg.get_or_insert_with(String::new);
+                    if in_quotes && chars.next_if_eq(&'"').is_some() {
+                        out.push('"');
+                        in_quotes = rules == WindowsRules::CrtArgv;
+                    } else {
+                        in_quotes = !in_quotes;
+                    }
+                }
+                _ => arg.get_or_insert_with(String::new).push(c),
+            }
+        }
+        args.extend(arg);
+        args
+    }
+
+    #[test]
+    fn test_parse() {
+        // The examples from Microsoft's "Parsing C command-line arguments".
+        let crt = WindowsRules::CrtArgv;
+        assert_eq!(parse(r#""abc" d e"#, crt), ["abc", "d", "e"]);
+        assert_eq!(parse(r#"a\\b d"e f"g h"#, crt), [r"a\\b", "de fg", "h"]);
+        assert_eq!(parse(r#"a\\\"b c d"#, crt), [r#"a\"b"#, "c", "d"]);
+        assert_eq!(parse(r#"a\\\\"b c" d e"#, crt), [r"a\\b c", "d", "e"]);
+        assert_eq!(parse(r#"a"b"" c d"#, crt), [r#"ab" c d"#]);
+        // ... which differs for CommandLineToArgvW.
+        assert_eq!(parse(r#"a"b"" c d"#, WindowsRules::CommandLineToArgvW), [r#"ab""#, "c", "d"]);
+    }
+
+    #[test]
+    fn test_quotemeta_windows() {
+        let crt = WindowsRules::CrtArgv;
+        let w = WindowsRules::CommandLineToArgvW;
+        assert_eq!(quotemeta_windows("", crt), r#""""#);
+        assert_eq!(quotemeta_windows("plain", w), "plain");
+        assert_eq!(quotemeta_windows(r"back\slash\", w), r"back\slash\");
+        assert_eq!(quotemeta_windows("a b", w), r#""a b""#);
+        assert_eq!(quotemeta_windows(r"a b\", w), r#""a b\\""#);
+        assert_eq!(quotemeta_windows(r#"a"b"#, crt), r#""a""b""#);
+        assert_eq!(quotemeta_windows(r#"a"b"#, w), r#""a\"b""#);
+        assert_eq!(quotemeta_windows(r#"a\"b"#, crt), r#""a\\""b""#);
+        assert_eq!(quotemeta_windows(r#"a\"b"#, w), r#""a\\\"b""#);
+        assert_eq!(quotemeta_windows(r#""""#, crt), r#""""""""#);
+
+        let inputs = ["", "a b", r"a\b", r"a\\ b\\", r#"a"b"#, r#"a\"b"#, r#"""#, r#"" c ""#];
+        for &rules in &[crt, w] {
+            for input in &inputs {
+                let line = format!("{} {}", quotemeta_windows(input, rules), input.len());
+                assert_eq!(parse(&line, rules), [String::from(*input), input.len().to_string()]);
+            }
+        }
+    }
+
+    #[test]
+    fn test_sanitize_windows_name() {
+        use WindowsNameIssue::{IllegalChar, Reserved, Trailing};
+
+        assert_eq!(sanitize_windows_name("CON"), (String::from("CON"), vec![Reserved]));
+        assert_eq!(sanitize_windows_name("Com1.tar.gz").1, [Reserved]);
+        assert_eq!(sanitize_windows_name("lpt9 .txt").1, [Reserved]);
+        assert_eq!(sanitize_windows_name("CONSOLE").1, []);
+        for name in ["com0", "LPT0.log", "CONIN$", "conout$.txt", "COM\u{b9}", "lpt\u{b3}.txt"] {
+            assert_eq!(sanitize_windows_name(name).1, [Reserved], "{name}");
+        }
+        assert_eq!(sanitize_windows_name("COM10").1, []);
+        assert_eq!(sanitize_windows_name("COM\u{2074}").1, []);
+        assert_eq!(sanitize_windows_name("CONIN").1, []);
+        assert_eq!(
+            sanitize_windows_name("trailing "),
+            (String::from("'trailing '"), vec![Trailing(' ')])
+        );
+        assert_eq!(sanitize_windows_name("dot.").1, [Trailing('.')]);
+        assert_eq!(sanitize_windows_name("why?"), (String::from("'why?'"), vec![IllegalChar('?')]));
+        assert_eq!(
+            sanitize_windows_name("nul.<?>?\n.").1,
+            [
+                Reserved,
+                IllegalChar('<'),
+                IllegalChar('?'),
+                IllegalChar('>'),
+                IllegalChar('\n'),
+                Trailing('.')
+            ]
+        );
+    }
+
+    #[test]
+    fn test_windows_safe_name() {
+        assert_eq!(&windows_safe_name("fine.txt"), "fine.txt");
+        assert_eq!(&windows_safe_name("CON"), "CON_");
+        assert_eq!(&windows_safe_name("aux.tar.gz"), "aux_.tar.gz");
+        assert_eq!(&windows_safe_name("trailing . "), "trailing");
+        assert_eq!(&windows_safe_name("why?"), "why_");
+        assert_eq!(&windows_safe_name("a<b>c:d"), "a_b_c_d");
+        assert_eq!(&windows_safe_name(". ."), "_");
+        assert_eq!(&windows_safe_name("nul ."), "nul_");
+        assert_eq!(&windows_safe_name("LPT0.log"), "LPT0_.log");
+        assert_eq!(&windows_safe_name("CONOUT$"), "CONOUT$_");
+        assert_eq!(&windows_safe_name("com\u{b2}.txt"), "com\u{b2}_.txt");
+        for name in &["CON", "trailing ", "why?", "lpt1 .txt", "a\tb."] {
+            assert_eq!(sanitize_windows_name(windows_safe_name(name)).1, []);
+        }
+    }
+}
@@ -0,0 +1,103 @@
+//! Quoting for the input of `xargs`, which has rules of its own.
+
+#[cfg(unix)] use std::os::unix::ffi::OsStrExt;
+use std::{path::Path, str};
+
+/// Returns whether `xargs` treats the character specially when reading its input.
+const fn is_xargs_special(c: char) -> bool {
+    c.is_ascii_whitespace() || c.is_ascii_control() || matches!(c, '\'' | '"' | '\\')
+}
+
+/// Quotes the given arguments for the standard input of `xargs`, one per line.
+///
+/// `xargs` splits its input at blanks and newlines, and understands `'...'`, `"..."` and backslash
+/// escapes, but none of the shell's other syntax. In particular, there is no `$'...'`, but neither
+/// do `$`, `*`, `;` and so on need quoting, since `xargs` runs the command directly rather than
+/// through a shell. Arguments are therefore left bare unless they contain a blank, quote or
+/// backslash, and are otherwise single-quoted, with single quotes written as `'\''` (that is,
+/// closing the quote, a backslash-escaped quote, and reopening it).
+///
+/// A quoted string may not span lines, so an argument containing a newline cannot be represented
+/// reliably, and nor can one which is not valid UTF-8 since the result is a `String`. In either
+/// case this returns `None`, and the arguments should instead be written NUL-terminated for
+/// `xargs -0`.
+///
+/// ```
+/// use quotemeta::quotemeta_xargs;
+///
+/// let input = quotemeta_xargs(["$HOME", "my file", "it's"]);
+/// assert_eq!(input.as_deref(), Some("$HOME\n'my file'\n'it'\\''s'\n"));
+/// assert_eq!(quotemeta_xargs(["two\nlines"]), None);
+/// ```
+pub fn quotemeta_xargs<I, S>(args: I) -> Option<String>
+where
+    I: IntoIterator<Item = S>,
+    S: AsRef<Path>,
+{
+    let mut out = String::new();
+    for arg in args {
+        let text = str::from_utf8(arg.as_ref().as_os_str().as_bytes()).ok()?;
+        if text.contains('\n') {
+            return None;
+        }
+        if text.is_empty() || text.contains(is_xargs_special) {
+            out.push('\'');
+            out.push_str(&text.replace('\'', r"'\''"));
+            out.push('\'');
+        } else {
+            out.push_str(text);
+        }
+        out.push('\n');
+    }
+    Some(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::quotemeta_xargs;
+    #[cfg(unix)] use std::os::unix::ffi::OsStrExt;
+    use std::ffi::OsStr;
+
+    /// Splits the input into arguments, as GNU `xargs` does.
+    fn xargs_words(s: &str) -> Vec<String> {
+        let (mut words, mut chars) = (Vec::new(), s.chars());
+        let mut word: Option<String> = None;
+        while let Some(c) = chars.next() {
+            match c {
+                ' ' | '\t' | '\n' => words.extend(word.take()),
+                '\'' | '"' => {
+                    let out = word.get_or_insert_with(String::new);
+                    for quoted in chars.by_ref() {
+                        assert_ne!(quoted, '\n', "unmatched quote");
+                        if quoted == c {
+                            break;
+                        }
+                        out.push(quoted);
+                    }
+                }
+                '\\' => word.get_or_insert_with(String::new).extend(chars.next()),
+                _ => word.get_or_insert_with(String::new).push(c),
+            }
+        }
+        words.extend(word);
+        words
+    }
+
+    #[test]
+    fn test_xargs_words() {
+        assert_eq!(xargs_words(" a  'b c'\n\"d'e\" f\\ g '' "), ["a", "b c", "d'e", "f g", ""]);
+    }
+
+    #[test]
+    fn test_quotemeta_xargs() {
+        let args =
+            ["plain", "", "a space", "tab\there", "it's", "\"quoted\"", r"back\slash", "$*;"];
+        let quoted = quotemeta_xargs(args).expect("representable");
+        assert_eq!(xargs_words(&quoted), args);
+        assert_eq!(quoted.lines().next(), Some("plain"));
+        assert_eq!(quoted.lines().last(), Some("$*;"));
+        assert_eq!(quotemeta_xargs(Vec::<&str>::new()).as_deref(), Some(""));
+        assert_eq!(quotemeta_xargs(["fine", "new\nline"]), None);
+        assert_eq!(quotemeta_xargs([OsStr::from_bytes(b"\xff")]), None);
+    }
+}